@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+
+use globset::GlobBuilder;
+use serde::Deserialize;
+
+const MANIFEST: &str = "Cargo.toml";
+
+#[derive(Deserialize)]
+struct Manifest {
+    package: Option<Package>,
+    workspace: Option<Workspace>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    version: Option<Inheritable>,
+}
+
+/// A key that is either set in the package itself, or inherited from the
+/// workspace with `key.workspace = true`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Inheritable {
+    Value(String),
+    Workspace { workspace: bool },
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    package: Option<WorkspacePackage>,
+}
+
+#[derive(Deserialize)]
+struct WorkspacePackage {
+    version: Option<String>,
+}
+
+/// A package in a Cargo workspace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub version: String,
+    /// The package's directory, relative to the workspace root.
+    pub path: PathBuf,
+}
+
+impl Manifest {
+    fn read(path: &Path) -> eyre::Result<Self> {
+        let string = std::fs::read_to_string(path)
+            .map_err(|error| eyre::eyre!("Couldn't read `{}`: {}", path.display(), error))?;
+
+        Ok(toml::from_str(&string)?)
+    }
+
+    /// The package's version, inheriting it from `workspace` if needed.
+    /// Packages without a version are `0.0.0`, as in Cargo.
+    fn version(&self, path: &Path, workspace: Option<&Workspace>) -> eyre::Result<String> {
+        let package = self
+            .package
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("`{}` has no `[package]`.", path.display()))?;
+
+        match &package.version {
+            None => Ok(String::from("0.0.0")),
+            Some(Inheritable::Value(version)) => Ok(version.clone()),
+            Some(Inheritable::Workspace { workspace: false }) => Err(eyre::eyre!(
+                "`{}` has an invalid `package.version`.",
+                path.display()
+            )),
+            Some(Inheritable::Workspace { workspace: true }) => workspace
+                .and_then(|workspace| workspace.package.as_ref())
+                .and_then(|package| package.version.clone())
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "`{}` inherits its version, but its workspace has no \
+                         `workspace.package.version`.",
+                        path.display()
+                    )
+                }),
+        }
+    }
+}
+
+/// Reads the version of `package` from the manifest at `path`. If `package`
+/// is `None` it is the manifest's own package, otherwise the manifest's own
+/// package or the workspace member with that name.
+pub fn package_version(path: &Path, package: Option<&str>) -> eyre::Result<String> {
+    let manifest = Manifest::read(path)?;
+    let own_package = manifest
+        .package
+        .as_ref()
+        .is_some_and(|own| package.is_none_or(|package| own.name == package));
+
+    if own_package {
+        if let Some(workspace) = &manifest.workspace {
+            return manifest.version(path, Some(workspace));
+        }
+        let root = workspace_root(path)?;
+        let workspace = root.and_then(|root| root.workspace);
+        return manifest.version(path, workspace.as_ref());
+    }
+
+    let package = package.ok_or_else(|| {
+        eyre::eyre!(
+            "`{}` is a virtual manifest, set the package to read the version of.",
+            path.display()
+        )
+    })?;
+
+    members(path)?
+        .into_iter()
+        .find(|member| member.name == package)
+        .map(|member| member.version)
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Package `{}` not found in the workspace of `{}`.",
+                package,
+                path.display()
+            )
+        })
+}
+
+/// Lists the members of the workspace whose root manifest is at `path`.
+pub fn members(path: &Path) -> eyre::Result<Vec<Member>> {
+    let manifest = Manifest::read(path)?;
+    let workspace = manifest
+        .workspace
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("`{}` has no `[workspace]`.", path.display()))?;
+    let root = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut excluded = Vec::new();
+    for pattern in &workspace.exclude {
+        excluded.extend(expand(root, pattern)?);
+    }
+
+    let mut members = Vec::new();
+    if let Some(package) = &manifest.package {
+        members.push(Member {
+            name: package.name.clone(),
+            version: manifest.version(path, Some(workspace))?,
+            path: PathBuf::new(),
+        });
+    }
+
+    for pattern in &workspace.members {
+        for directory in expand(root, pattern)? {
+            let member_path = directory.join(MANIFEST);
+            if excluded.contains(&directory) || !member_path.is_file() {
+                continue;
+            }
+
+            let member = Manifest::read(&member_path)?;
+            let name = match &member.package {
+                Some(package) => package.name.clone(),
+                None => continue,
+            };
+            members.push(Member {
+                name,
+                version: member.version(&member_path, Some(workspace))?,
+                path: directory.strip_prefix(root)?.to_owned(),
+            });
+        }
+    }
+
+    Ok(members)
+}
+
+/// Finds the manifest of the workspace containing the manifest at `path`.
+fn workspace_root(path: &Path) -> eyre::Result<Option<Manifest>> {
+    let directory = path.canonicalize()?;
+
+    for ancestor in directory.ancestors().skip(2) {
+        let path = ancestor.join(MANIFEST);
+        if path.is_file() {
+            let manifest = Manifest::read(&path)?;
+            if manifest.workspace.is_some() {
+                return Ok(Some(manifest));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Expands the glob `pattern` of workspace members, relative to `root`.
+fn expand(root: &Path, pattern: &str) -> eyre::Result<Vec<PathBuf>> {
+    let mut paths = vec![root.to_owned()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !component.contains(&['*', '?', '[', '{'][..]) {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        }
+
+        let glob = GlobBuilder::new(component)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        let mut expanded = Vec::new();
+        for path in paths.iter().filter(|path| path.is_dir()) {
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() && glob.is_match(entry.file_name()) {
+                    expanded.push(entry.path());
+                }
+            }
+        }
+        expanded.sort();
+        paths = expanded;
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a workspace in a temporary directory from `(path, contents)`
+    /// pairs.
+    fn workspace(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("relnotes-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn reads_package_versions() {
+        let root = workspace(
+            "versions",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n\
+                     [workspace.package]\nversion = \"1.2.0\"\n",
+                ),
+                (
+                    "crates/render/Cargo.toml",
+                    "[package]\nname = \"render\"\nversion.workspace = true\n",
+                ),
+                (
+                    "crates/audio/Cargo.toml",
+                    "[package]\nname = \"audio\"\nversion = \"0.3.1\"\n",
+                ),
+                (
+                    "crates/old/Cargo.toml",
+                    "[package]\nname = \"old\"\nversion = \"0.1.0\"\n",
+                ),
+            ],
+        );
+        let manifest = root.join("Cargo.toml");
+
+        assert_eq!(package_version(&manifest, Some("render")).unwrap(), "1.2.0");
+        assert_eq!(package_version(&manifest, Some("audio")).unwrap(), "0.3.1");
+        assert!(package_version(&manifest, Some("old")).is_err());
+        assert!(package_version(&manifest, None).is_err());
+        assert_eq!(
+            package_version(&root.join("crates/render/Cargo.toml"), None).unwrap(),
+            "1.2.0"
+        );
+        assert_eq!(
+            members(&manifest)
+                .unwrap()
+                .into_iter()
+                .map(|member| member.path)
+                .collect::<Vec<_>>(),
+            [
+                PathBuf::from("crates/audio"),
+                PathBuf::from("crates/render")
+            ]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
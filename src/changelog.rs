@@ -0,0 +1,120 @@
+use regex::Regex;
+
+pub const DEFAULT_MARKER: &str = "<!-- next-header -->";
+
+/// Inserts `notes` for `version` into `changelog`, on the line after
+/// `marker`, or after the changelog's `# Changelog` title if it has no
+/// marker, or otherwise at the top. Errors if `changelog` already has a
+/// heading for `version`.
+pub fn prepend(changelog: &str, notes: &str, version: &str, marker: &str) -> eyre::Result<String> {
+    if has_version(changelog, version) {
+        return Err(crate::error::Error::Config(format!(
+            "The changelog already has a section for `{}`.",
+            version
+        ))
+        .into());
+    }
+
+    let index = match changelog.find(marker) {
+        Some(index) => line_end(changelog, index + marker.len()),
+        None => changelog
+            .lines()
+            .next()
+            .filter(|line| line.starts_with("# ") && line.to_lowercase().contains("changelog"))
+            .map_or(0, |_| line_end(changelog, 0)),
+    };
+    let (head, tail) = changelog.split_at(index);
+
+    let mut output = String::from(head);
+    if !head.is_empty() {
+        output.push('\n');
+    }
+    output.push_str(notes.trim());
+    output.push('\n');
+    let tail = tail.trim_start_matches(&['\r', '\n'][..]);
+    if !tail.is_empty() {
+        output.push('\n');
+        output.push_str(tail);
+    }
+
+    Ok(output)
+}
+
+/// The index after the end of the line containing `index`.
+fn line_end(string: &str, index: usize) -> usize {
+    string[index..]
+        .find('\n')
+        .map_or(string.len(), |end| index + end + 1)
+}
+
+/// Whether `changelog` has a heading containing `version`, optionally
+/// prefixed with `v`.
+fn has_version(changelog: &str, version: &str) -> bool {
+    let regex = Regex::new(&format!(
+        r"(?m)^#+ (?:.*[^\w.-])?v?{}(?:[^\w.-].*)?$",
+        regex::escape(version)
+    ))
+    .unwrap();
+
+    regex.is_match(changelog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &str = "# relnotes 0.3.0\n\n- Fix crash\n";
+
+    #[test]
+    fn inserts_after_marker() {
+        assert_eq!(
+            prepend(
+                "# Changelog\n\nIntro.\n<!-- next-header -->\n\n# relnotes 0.2.0\n",
+                NOTES,
+                "0.3.0",
+                DEFAULT_MARKER
+            )
+            .unwrap(),
+            "# Changelog\n\nIntro.\n<!-- next-header -->\n\n\
+             # relnotes 0.3.0\n\n- Fix crash\n\n# relnotes 0.2.0\n"
+        );
+    }
+
+    #[test]
+    fn inserts_after_title() {
+        assert_eq!(
+            prepend("# Changelog\n\n## v0.2.0\n", NOTES, "0.3.0", DEFAULT_MARKER).unwrap(),
+            "# Changelog\n\n# relnotes 0.3.0\n\n- Fix crash\n\n## v0.2.0\n"
+        );
+        assert_eq!(
+            prepend("# relnotes 0.2.0\n", NOTES, "0.3.0", DEFAULT_MARKER).unwrap(),
+            "# relnotes 0.3.0\n\n- Fix crash\n\n# relnotes 0.2.0\n"
+        );
+        assert_eq!(
+            prepend("", NOTES, "0.3.0", DEFAULT_MARKER).unwrap(),
+            "# relnotes 0.3.0\n\n- Fix crash\n"
+        );
+    }
+
+    #[test]
+    fn refuses_existing_versions() {
+        assert!(prepend(
+            "# Changelog\n\n## [v0.3.0] - 2021-01-01\n",
+            NOTES,
+            "0.3.0",
+            DEFAULT_MARKER
+        )
+        .is_err());
+        assert!(prepend(
+            "# relnotes 0.3.0 (2021-01-01)\n",
+            NOTES,
+            "0.3.0",
+            DEFAULT_MARKER
+        )
+        .is_err());
+        assert!(!has_version(
+            "# relnotes 10.3.0\n## 0.3.0-alpha\nFixed in 0.3.0\n",
+            "0.3.0"
+        ));
+    }
+}
@@ -1,11 +1,22 @@
+pub mod breaking_changes;
+pub mod release_notes;
 pub mod template;
 pub mod timeframe;
+pub mod version_bump;
 
+use std::path::{Component, Path, PathBuf};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
 use regex::RegexSet;
 use serde::Deserialize;
 
+use crate::forge::ChangeEntry;
+
+pub use breaking_changes::*;
+pub use release_notes::*;
 pub use template::*;
 pub use timeframe::*;
+pub use version_bump::*;
 
 fn default_from() -> Timeframe {
     Timeframe::Release(ReleaseKind::Latest)
@@ -21,12 +32,119 @@ fn default_date_format() -> String {
     String::from(DATE_FORMAT)
 }
 
+/// The forge to retrieve changes and releases from.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provider {
+    #[default]
+    #[serde(rename = "github")]
+    GitHub,
+    #[serde(rename = "gitlab")]
+    GitLab,
+    LocalGit,
+}
+
+impl std::str::FromStr for Provider {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "gitlab" => Ok(Self::GitLab),
+            "local-git" => Ok(Self::LocalGit),
+            _ => Err(eyre::eyre!(
+                "Provider must be one of `github`, `gitlab`, or `local-git`."
+            )),
+        }
+    }
+}
+
+/// How a category's matchers are combined.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Matching {
+    /// Any of the matchers has to match.
+    #[default]
+    Any,
+    /// Every matcher that's been set has to match.
+    All,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Category {
     pub title: String,
+    pub description: Option<String>,
+    /// Categories are sorted by `order`, and then by the order they appear
+    /// in the configuration.
+    #[serde(default)]
+    pub order: i64,
     #[serde(deserialize_with = "from_regex_set")]
     #[serde(default = "default_regex_set")]
     pub labels: RegexSet,
+    /// Matched against the type of conventional commit titles.
+    #[serde(deserialize_with = "from_regex_set")]
+    #[serde(default = "default_regex_set")]
+    pub types: RegexSet,
+    /// Matched against the scope of conventional commit titles.
+    #[serde(deserialize_with = "from_regex_set")]
+    #[serde(default = "default_regex_set")]
+    pub scopes: RegexSet,
+    /// Matched against the title of the change.
+    #[serde(deserialize_with = "from_regex_set")]
+    #[serde(default = "default_regex_set")]
+    pub titles: RegexSet,
+    /// Matched against the body of the change.
+    #[serde(deserialize_with = "from_regex_set")]
+    #[serde(default = "default_regex_set")]
+    pub bodies: RegexSet,
+    /// Globs matched against the paths of the files the change touched.
+    #[serde(deserialize_with = "from_glob_set")]
+    #[serde(default = "default_glob_set")]
+    pub paths: GlobSet,
+    #[serde(rename = "match")]
+    #[serde(default)]
+    pub matching: Matching,
+}
+
+impl Category {
+    /// Whether `change` belongs in this category, according to whether
+    /// `any` or `all` of the category's `labels`, conventional commit
+    /// (`types` and `scopes`), `titles`, `bodies`, and `paths` matchers
+    /// match. Matchers that haven't been set are ignored.
+    pub fn matches(&self, change: &ChangeEntry) -> bool {
+        let results = [
+            (!self.labels.is_empty())
+                .then(|| change.labels.iter().any(|l| self.labels.is_match(&l.name))),
+            (!self.types.is_empty() || !self.scopes.is_empty())
+                .then(|| self.matches_conventional(change)),
+            (!self.titles.is_empty()).then(|| self.titles.is_match(&change.title)),
+            (!self.bodies.is_empty()).then(|| {
+                change
+                    .body
+                    .as_deref()
+                    .is_some_and(|body| self.bodies.is_match(body))
+            }),
+            (!self.paths.is_empty())
+                .then(|| change.files.iter().any(|file| self.paths.is_match(file))),
+        ];
+        let mut results = results.iter().flatten().peekable();
+
+        match self.matching {
+            Matching::Any => results.any(|matched| *matched),
+            Matching::All => results.peek().is_some() && results.all(|matched| *matched),
+        }
+    }
+
+    fn matches_conventional(&self, change: &ChangeEntry) -> bool {
+        change.conventional.as_ref().is_some_and(|conventional| {
+            (self.types.is_empty() || self.types.is_match(&conventional.kind))
+                && (self.scopes.is_empty()
+                    || conventional
+                        .scope
+                        .as_ref()
+                        .is_some_and(|scope| self.scopes.is_match(scope)))
+        })
+    }
 }
 
 fn default_regex_set() -> RegexSet {
@@ -44,11 +162,9 @@ where
     if list.is_none() {
         return Ok(None);
     }
-    #[allow(clippy::map_err_ignore)]
-    // ignore because we cannot implement a foreign trait on a foreign struct
     regex::RegexSet::new(list.unwrap())
         .map(Some)
-        .map_err(|_| serde::de::Error::custom("Category labels were not valid regular expressions"))
+        .map_err(serde::de::Error::custom)
 }
 
 fn from_regex_set<'de, D>(de: D) -> Result<RegexSet, D::Error>
@@ -64,13 +180,134 @@ where
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// The glob matching the files in `directory`, a path relative to the root
+/// of the repository. Forges separate paths with `/` on every platform.
+fn directory_glob(directory: &Path) -> Result<Glob, globset::Error> {
+    let mut pattern = directory
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(escape_glob(&name.to_string_lossy())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    pattern.push(String::from("**"));
+
+    GlobBuilder::new(&pattern.join("/"))
+        .literal_separator(true)
+        .build()
+}
+
+/// Escapes the characters glob patterns treat specially in `name`.
+fn escape_glob(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '*' | '?' | '[' | ']' | '{' | '}' | '\\' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn default_glob_set() -> GlobSet {
+    GlobSet::empty()
+}
+
+fn from_optional_glob_set<'de, D>(de: D) -> Result<Option<GlobSet>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let list: Option<Vec<String>> = <_>::deserialize(de)?;
+
+    let list = match list {
+        Some(list) => list,
+        None => return Ok(None),
+    };
+    let mut builder = GlobSetBuilder::new();
+
+    for glob in list {
+        builder.add(
+            GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .map_err(serde::de::Error::custom)?,
+        );
+    }
+
+    builder.build().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn from_glob_set<'de, D>(de: D) -> Result<GlobSet, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let glob_set = from_optional_glob_set(de)?;
+
+    if let Some(glob_set) = glob_set {
+        Ok(glob_set)
+    } else {
+        Err(serde::de::Error::custom("Path GlobSet not found."))
+    }
+}
+
+const fn default_jobs() -> usize {
+    8
+}
+
+fn default_changelog_marker() -> String {
+    String::from(crate::changelog::DEFAULT_MARKER)
+}
+
+fn default_cargo_manifest() -> PathBuf {
+    PathBuf::from("Cargo.toml")
+}
+
+/// Where to read the version of the release from, instead of the command
+/// line.
+#[derive(Clone, Debug, Deserialize)]
+pub struct VersionFrom {
+    /// The path of the manifest, relative to the current directory.
+    #[serde(default = "default_cargo_manifest")]
+    pub cargo: PathBuf,
+    /// The package in the manifest's workspace to read the version of.
+    /// Default: the manifest's own package.
+    pub package: Option<String>,
+}
+
+impl Default for VersionFrom {
+    fn default() -> Self {
+        Self {
+            cargo: default_cargo_manifest(),
+            package: None,
+        }
+    }
+}
+
+impl VersionFrom {
+    pub fn version(&self) -> eyre::Result<String> {
+        crate::cargo::package_version(&self.cargo, self.package.as_deref())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
     #[serde(default = "default_from")]
     pub from: Timeframe,
     #[serde(default = "default_to")]
     pub to: Timeframe,
+    /// What `release:latest` resolves to if there are no releases yet.
+    #[serde(default)]
+    pub on_missing_release: OnMissingRelease,
+    #[serde(default)]
+    pub provider: Provider,
+    pub provider_url: Option<String>,
+    pub repository_path: Option<PathBuf>,
     pub owner: String,
     pub repo: String,
     pub title: Option<String>,
@@ -79,31 +316,82 @@ pub struct Config {
     #[serde(deserialize_with = "from_regex_set")]
     #[serde(default = "default_regex_set")]
     pub skip_labels: RegexSet,
+    /// Only PRs which changed files matching these globs are included.
+    #[serde(deserialize_with = "from_glob_set")]
+    #[serde(default = "default_glob_set")]
+    pub paths: GlobSet,
+    /// Changed files matching these globs are ignored when matching `paths`.
+    #[serde(deserialize_with = "from_glob_set")]
+    #[serde(default = "default_glob_set")]
+    pub exclude_paths: GlobSet,
     #[serde(default)]
     pub categories: Vec<Category>,
+    #[serde(default)]
+    pub release_notes: ReleaseNotes,
+    #[serde(default)]
+    pub breaking_changes: BreakingChanges,
+    #[serde(default)]
+    pub version_bump: VersionBump,
+    pub version_from: Option<VersionFrom>,
+    /// The manifest of a Cargo workspace to generate release notes for each
+    /// member crate of, relative to the root of the repository.
+    pub workspace: Option<PathBuf>,
+    /// How many changes to fetch the details of at once, per repository.
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    /// The line of the changelog to insert new release notes after.
+    #[serde(default = "default_changelog_marker")]
+    pub changelog_marker: String,
     pub template: Template,
     #[serde(default)]
     includes: Vec<IncludeConfig>,
     #[serde(default)]
-    parent: Option<(String, String)>,
+    pub parent: Option<(String, String)>,
 }
 
 impl Config {
     pub fn new(owner: String, repo: String) -> Self {
         Self {
+            breaking_changes: BreakingChanges::default(),
             categories: Vec::new(),
+            changelog_marker: default_changelog_marker(),
             date_format: default_date_format(),
+            exclude_paths: default_glob_set(),
             from: default_from(),
             includes: Vec::new(),
+            jobs: default_jobs(),
+            on_missing_release: OnMissingRelease::default(),
             owner,
             parent: None,
+            paths: default_glob_set(),
+            provider: Provider::default(),
+            provider_url: None,
+            release_notes: ReleaseNotes::default(),
             repo,
+            repository_path: None,
             skip_labels: default_regex_set(),
             template: Template::default(),
             title: None,
             to: default_to(),
+            version_bump: VersionBump::default(),
+            version_from: None,
+            workspace: None,
         }
     }
+
+    /// Whether `change` is within the paths of the repository covered by
+    /// this configuration, i.e. it changed at least one file that matches
+    /// `paths` and doesn't match `exclude_paths`.
+    pub fn is_in_scope(&self, change: &ChangeEntry) -> bool {
+        if self.paths.is_empty() && self.exclude_paths.is_empty() {
+            return true;
+        }
+
+        change.files.iter().any(|file| {
+            (self.paths.is_empty() || self.paths.is_match(file))
+                && !self.exclude_paths.is_match(file)
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -118,6 +406,12 @@ pub struct IncludeConfig {
     #[serde(deserialize_with = "from_optional_regex_set")]
     #[serde(default)]
     pub skip_labels: Option<RegexSet>,
+    #[serde(deserialize_with = "from_optional_glob_set")]
+    #[serde(default)]
+    pub paths: Option<GlobSet>,
+    #[serde(deserialize_with = "from_optional_glob_set")]
+    #[serde(default)]
+    pub exclude_paths: Option<GlobSet>,
     pub categories: Option<Vec<Category>>,
     #[serde(default)]
     pub uses_root_timeframe: bool,
@@ -143,12 +437,179 @@ impl Config {
                     to: ic.to.unwrap_or_else(|| self.to.clone()),
                     date_format: ic.date_format.unwrap_or_else(|| self.date_format.clone()),
                     skip_labels: ic.skip_labels.unwrap_or_else(|| self.skip_labels.clone()),
+                    paths: ic.paths.unwrap_or_else(|| self.paths.clone()),
+                    exclude_paths: ic
+                        .exclude_paths
+                        .unwrap_or_else(|| self.exclude_paths.clone()),
                     categories: ic.categories.unwrap_or_else(|| self.categories.clone()),
-                    template: self.template.clone(),
+                    version_from: None,
+                    workspace: None,
                     includes: Vec::new(),
                     parent,
+                    ..self.clone()
                 }
             })
             .collect()
     }
+
+    /// The configuration of the workspace member `package`, covering only
+    /// the files in its directory (`directory`, relative to the root of the
+    /// repository) since the release `from`. `others` are the directories of
+    /// the other members, which are excluded when `package` is the
+    /// workspace's root package.
+    pub fn for_package(
+        &self,
+        package: &str,
+        directory: &Path,
+        others: &[PathBuf],
+        from: Timeframe,
+    ) -> eyre::Result<Self> {
+        let paths = GlobSetBuilder::new()
+            .add(directory_glob(directory)?)
+            .build()?;
+        let mut exclude_paths = GlobSetBuilder::new();
+        for other in others.iter().filter(|other| other.starts_with(directory)) {
+            exclude_paths.add(directory_glob(other)?);
+        }
+
+        Ok(Self {
+            title: Some(package.to_owned()),
+            from,
+            paths,
+            exclude_paths: exclude_paths.build()?,
+            version_from: None,
+            workspace: None,
+            includes: Vec::new(),
+            parent: None,
+            ..self.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::change;
+
+    fn category(toml: &str) -> Category {
+        toml::from_str(&format!("title = \"Category\"\n{}", toml)).unwrap()
+    }
+
+    #[test]
+    fn matches_any_matcher() {
+        let category = category(
+            r#"
+            labels = ["bug"]
+            titles = ["\\[breaking\\]"]
+            bodies = ["(?m)^Changelog: fixed$"]
+            "#,
+        );
+
+        assert!(category.matches(&change("Fix crash", "", &["bug"])));
+        assert!(category.matches(&change("[breaking] Rename", "", &[])));
+        assert!(category.matches(&change("Fix", "Details\nChangelog: fixed", &[])));
+        assert!(!category.matches(&change("Add feature", "Changelog: added", &["feature"])));
+    }
+
+    #[test]
+    fn matches_all_matchers() {
+        let category = category(
+            r#"
+            match = "all"
+            types = ["fix"]
+            bodies = ["(?m)^Changelog: fixed$"]
+            "#,
+        );
+
+        assert!(category.matches(&change("fix: Crash", "Changelog: fixed", &[])));
+        assert!(!category.matches(&change("fix: Crash", "", &[])));
+        assert!(!category.matches(&change("Crash", "Changelog: fixed", &[])));
+    }
+
+    #[test]
+    fn matches_paths() {
+        let category = category(r#"paths = ["crates/render/**"]"#);
+        let mut change = change("Fix shadows", "", &[]);
+
+        assert!(!category.matches(&change));
+        change.files = vec![String::from("README.md")];
+        assert!(!category.matches(&change));
+        change
+            .files
+            .push(String::from("crates/render/src/shadows.rs"));
+        assert!(category.matches(&change));
+    }
+
+    #[test]
+    fn scopes_to_paths() {
+        let config: Config = toml::from_str(
+            r#"
+            owner = "EmbarkStudios"
+            repo = "relnotes"
+            paths = ["crates/render/**"]
+            exclude-paths = ["**/*.md"]
+            [template]
+            "#,
+        )
+        .unwrap();
+        let mut change = change("Fix shadows", "", &[]);
+
+        assert!(!config.is_in_scope(&change));
+        change.files = vec![String::from("crates/render/README.md")];
+        assert!(!config.is_in_scope(&change));
+        change
+            .files
+            .push(String::from("crates/render/src/shadows.rs"));
+        assert!(config.is_in_scope(&change));
+        assert!(Config::new(String::new(), String::new()).is_in_scope(&change));
+    }
+
+    #[test]
+    fn scopes_packages_to_their_directory() {
+        let config = Config::new(String::new(), String::new());
+        let others = [PathBuf::from("crates/render")];
+        let root = config
+            .for_package("engine", Path::new(""), &others, default_from())
+            .unwrap();
+        let render = config
+            .for_package("render", &others[0], &[PathBuf::new()], default_from())
+            .unwrap();
+        let mut change = change("Fix shadows", "", &[]);
+
+        change.files = vec![String::from("crates/render/src/shadows.rs")];
+        assert!(render.is_in_scope(&change));
+        assert!(!root.is_in_scope(&change));
+        change.files = vec![String::from("src/main.rs")];
+        assert!(!render.is_in_scope(&change));
+        assert!(root.is_in_scope(&change));
+    }
+
+    #[test]
+    fn reports_invalid_regular_expressions() {
+        let error = toml::from_str::<Category>("title = \"Category\"\ntitles = [\"(\"]")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unclosed group"), "{}", error);
+    }
+
+    #[test]
+    fn scopes_packages_with_special_characters() {
+        let config = Config::new(String::new(), String::new());
+        let directory = Path::new("crates").join("[render]*");
+        let render = config
+            .for_package("render", &directory, &[], default_from())
+            .unwrap();
+        let mut change = change("Fix shadows", "", &[]);
+
+        change.files = vec![String::from("crates/[render]*/src/shadows.rs")];
+        assert!(render.is_in_scope(&change));
+        change.files = vec![String::from("crates/r/src/shadows.rs")];
+        assert!(!render.is_in_scope(&change));
+    }
+
+    #[test]
+    fn matches_nothing_without_matchers() {
+        assert!(!category("").matches(&change("fix: Crash", "", &["bug"])));
+        assert!(!category("match = \"all\"").matches(&change("fix: Crash", "", &["bug"])));
+    }
 }
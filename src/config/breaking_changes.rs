@@ -0,0 +1,102 @@
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+
+use crate::forge::ChangeEntry;
+
+const fn default_commits() -> bool {
+    true
+}
+
+/// How to detect PRs that contain breaking changes.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BreakingChanges {
+    /// PRs with labels matching these are breaking.
+    #[serde(deserialize_with = "super::from_regex_set")]
+    #[serde(default = "super::default_regex_set")]
+    pub labels: RegexSet,
+    /// Whether to search the PR's commits for `BREAKING CHANGE:` footers,
+    /// as well as its description.
+    #[serde(default = "default_commits")]
+    pub commits: bool,
+}
+
+impl Default for BreakingChanges {
+    fn default() -> Self {
+        Self {
+            labels: super::default_regex_set(),
+            commits: default_commits(),
+        }
+    }
+}
+
+impl BreakingChanges {
+    /// Whether `change` is breaking, because of its labels, a `!` in its
+    /// conventional commit title, or a `BREAKING CHANGE:` footer in its body
+    /// or `commits`. Returns the text of any footers as the migration note.
+    pub fn detect(&self, change: &ChangeEntry, commits: &[String]) -> (bool, Option<String>) {
+        let footers = change
+            .body
+            .iter()
+            .chain(commits)
+            .flat_map(|message| footers(message))
+            .collect::<Vec<_>>();
+        // The same footer is often in both the description and a commit.
+        let mut notes: Vec<&str> = Vec::new();
+        for note in footers.iter().filter(|note| !note.is_empty()) {
+            if !notes.contains(&&**note) {
+                notes.push(note);
+            }
+        }
+
+        let breaking = !footers.is_empty()
+            || change
+                .conventional
+                .as_ref()
+                .is_some_and(|conventional| conventional.breaking)
+            || change.labels.iter().any(|l| self.labels.is_match(&l.name));
+
+        let note = Some(notes.join("\n\n")).filter(|note| !note.is_empty());
+
+        (breaking, note)
+    }
+}
+
+/// Returns the text of every `BREAKING CHANGE:` footer in `message`, which
+/// continues until the next blank line. The text may be empty.
+fn footers(message: &str) -> Vec<String> {
+    static FOOTER: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?m)^BREAKING[ -]CHANGE: *(?P<note>.*(?:\r?\n[^\r\n]+)*)").unwrap()
+    });
+
+    FOOTER
+        .captures_iter(message)
+        .map(|captures| captures["note"].trim().to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_footers() {
+        assert_eq!(
+            footers(
+                "Rename `run`\n\nBREAKING CHANGE: `run` is now `execute`.\nUpdate your calls.\n\n\
+                 Reviewed-by: someone\nBREAKING-CHANGE: `stop` was removed."
+            ),
+            [
+                "`run` is now `execute`.\nUpdate your calls.",
+                "`stop` was removed."
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_mentions_in_text() {
+        assert!(footers("This is not a BREAKING CHANGE: really.").is_empty());
+        assert_eq!(footers("BREAKING CHANGE:"), [""]);
+    }
+}
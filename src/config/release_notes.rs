@@ -0,0 +1,154 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+fn default_headings() -> Vec<String> {
+    vec![String::from("Release notes"), String::from("Release note")]
+}
+
+fn default_fences() -> Vec<String> {
+    vec![String::from("release-note")]
+}
+
+/// Where to find the user facing release note in the body of a PR.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReleaseNotes {
+    /// Markdown headings whose section contains the release note. Compared
+    /// case-insensitively.
+    #[serde(default = "default_headings")]
+    pub headings: Vec<String>,
+    /// Info strings of code blocks containing the release note.
+    #[serde(default = "default_fences")]
+    pub fences: Vec<String>,
+    /// Skip PRs whose release note is `NONE`.
+    #[serde(default)]
+    pub skip_none: bool,
+}
+
+impl Default for ReleaseNotes {
+    fn default() -> Self {
+        Self {
+            headings: default_headings(),
+            fences: default_fences(),
+            skip_none: false,
+        }
+    }
+}
+
+/// The release note of a PR.
+#[derive(Debug, PartialEq)]
+pub enum ReleaseNote {
+    Note(String),
+    /// The PR explicitly has no release note.
+    None,
+}
+
+impl ReleaseNotes {
+    /// Extracts the release note from `body`, preferring a code block over
+    /// a heading's section. HTML comments are removed from the note.
+    pub fn extract(&self, body: &str) -> Option<ReleaseNote> {
+        static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+        let body = COMMENT.replace_all(body, "");
+        let note = self
+            .extract_fence(&body)
+            .or_else(|| self.extract_section(&body))?;
+        let note = note.trim();
+
+        if note.is_empty() {
+            None
+        } else if note.eq_ignore_ascii_case("none") {
+            Some(ReleaseNote::None)
+        } else {
+            Some(ReleaseNote::Note(note.to_owned()))
+        }
+    }
+
+    fn extract_fence(&self, body: &str) -> Option<String> {
+        let mut lines = body.lines();
+        lines.find(|line| {
+            line.trim()
+                .strip_prefix("```")
+                .is_some_and(|info| self.fences.iter().any(|fence| fence == info.trim()))
+        })?;
+
+        Some(
+            lines
+                .take_while(|line| !line.trim().starts_with("```"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    fn extract_section(&self, body: &str) -> Option<String> {
+        static HEADING: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"^(?P<level>#{1,6})\s+(?P<text>.*?)\s*#*\s*$").unwrap());
+
+        let mut lines = body.lines();
+        let level = lines.find_map(|line| {
+            let captures = HEADING.captures(line)?;
+            self.headings
+                .iter()
+                .any(|heading| heading.eq_ignore_ascii_case(&captures["text"]))
+                .then(|| captures["level"].len())
+        })?;
+
+        Some(
+            lines
+                .take_while(|line| {
+                    HEADING
+                        .captures(line)
+                        .is_none_or(|captures| captures["level"].len() > level)
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(body: &str) -> Option<ReleaseNote> {
+        ReleaseNotes::default().extract(body)
+    }
+
+    #[test]
+    fn extracts_fenced_note() {
+        assert_eq!(
+            note("Refactors the renderer.\n\n```release-note\nShadows are now softer.\n```\n"),
+            Some(ReleaseNote::Note(String::from("Shadows are now softer.")))
+        );
+    }
+
+    #[test]
+    fn extracts_heading_section() {
+        assert_eq!(
+            note(
+                "## Description\nRefactors the renderer.\n\n\
+                 ### Release notes\n<!-- Describe the change for users -->\n\
+                 Shadows are now softer.\n#### Details\nMore.\n\
+                 ## Checklist\n- [x] Tests"
+            ),
+            Some(ReleaseNote::Note(String::from(
+                "Shadows are now softer.\n#### Details\nMore."
+            )))
+        );
+    }
+
+    #[test]
+    fn recognises_none() {
+        assert_eq!(note("```release-note\nNONE\n```"), Some(ReleaseNote::None));
+    }
+
+    #[test]
+    fn missing_note() {
+        assert_eq!(note("Refactors the renderer."), None);
+        assert_eq!(
+            note("### Release notes\n<!-- Describe the change -->\n"),
+            None
+        );
+    }
+}
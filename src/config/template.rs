@@ -5,14 +5,22 @@ use serde::Deserialize;
 const DEFAULT_TEMPLATE: &str = "\
 # {{ title }} {{ version }} ({{ date }})
 
+{% if breaking -%}
+## Breaking Changes
+
+{% for pr in breaking -%}
+- [{{ pr.title }}]({{ pr.html_url }})
+{% endfor %}
+{% endif -%}
+
 {% for pr in prs -%}
 - [{{ pr.title }}]({{ pr.html_url }})
 {% endfor %}
 
-{%- for title, prs in categories %}
-## {{ title }}
+{%- for category in categories %}
+## {{ category.title }}
 
-    {% for pr in prs %}
+    {% for pr in category.prs %}
 - [{{ pr.title }}]({{ pr.html_url }})
     {%- endfor %}
 {% endfor %}
@@ -20,10 +28,10 @@ const DEFAULT_TEMPLATE: &str = "\
 {%- for include in includes %}
 ## {{ include.title }}
 
-    {% for title, prs in include.categories %}
-### {{ title }}
+    {% for category in include.categories %}
+### {{ category.title }}
 
-        {%- for pr in prs %}
+        {%- for pr in category.prs %}
 - [{{ pr.title }}]({{ pr.html_url }})
         {%- endfor %}
 
@@ -1,6 +1,10 @@
 use chrono::{Date, DateTime, NaiveDate, Utc};
 
 use super::Config;
+use crate::{
+    error::Error,
+    forge::{Forge, Release},
+};
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub enum DateKind {
@@ -15,80 +19,138 @@ pub enum ReleaseKind {
     RelativeFromLast(u8),
 }
 
+/// What `release:latest` resolves to when the repository has no releases
+/// yet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnMissingRelease {
+    /// The repository's creation date, or the date of its first commit.
+    #[default]
+    RepoStart,
+    /// Fail with an error.
+    Error,
+}
+
+/// A resolved `Timeframe`.
+#[derive(Clone, Debug)]
+pub struct Resolved {
+    pub date: DateTime<Utc>,
+    /// The git reference the date was resolved from, if any.
+    pub reference: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 pub enum Timeframe {
     Release(ReleaseKind),
     Date(DateKind),
+    /// A git reference such as a tag, branch, or commit.
+    Reference(String),
 }
 
 impl Timeframe {
-    pub async fn date_from_timeframe(
-        &self,
-        octocrab: &octocrab::Octocrab,
-        config: &Config,
-    ) -> eyre::Result<DateTime<Utc>> {
+    /// Resolves the date this timeframe refers to, and the git reference
+    /// (usually a release's tag) it was resolved from, if any.
+    pub async fn resolve(&self, forge: &dyn Forge, config: &Config) -> eyre::Result<Resolved> {
         let (owner, repo) = config
             .parent
             .clone()
             .unwrap_or_else(|| (config.owner.clone(), config.repo.clone()));
         Ok(match self {
             Timeframe::Release(ReleaseKind::Latest) => {
-                octocrab
-                    .repos(&owner, &repo)
-                    .releases()
-                    .get_latest()
-                    .await?
-                    .published_at
+                match latest_release(forge, config, &owner, &repo).await? {
+                    Some(release) => Resolved {
+                        date: release.published_at,
+                        reference: Some(release.tag_name),
+                    },
+                    None => Resolved {
+                        date: forge.repository_start(&owner, &repo).await?,
+                        reference: None,
+                    },
+                }
             }
             Timeframe::Release(ReleaseKind::RelativeFromLast(number)) => {
-                let page = octocrab
-                    .repos(&owner, &repo)
-                    .releases()
-                    .list()
-                    .per_page(100)
-                    .send()
-                    .await?;
-
-                let mut next = page.next;
-                let mut releases = page.items;
-                while let Some(mut page) = octocrab.get_page(&next).await? {
-                    releases.append(&mut page.items);
-                    next = page.next;
+                let release = nth_latest_release(forge, &owner, &repo, *number).await?;
+                Resolved {
+                    date: release.created_at,
+                    reference: Some(release.tag_name),
                 }
-
-                releases.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-
-                releases
-                    .get(*number as usize)
-                    .unwrap_or_else(|| {
-                        panic!(
-                            "Expected at least {} releases, but only {} found.",
-                            number,
-                            releases.len()
-                        )
-                    })
-                    .created_at
             }
             Timeframe::Release(ReleaseKind::Absolute(tag)) => {
-                octocrab
-                    .repos(&owner, &repo)
-                    .releases()
-                    .get_by_tag(tag)
-                    .await?
-                    .published_at
+                let release = forge.release(&owner, &repo, tag).await?;
+                Resolved {
+                    date: release.published_at,
+                    reference: Some(release.tag_name),
+                }
             }
-            Timeframe::Date(DateKind::Today) => Utc::now(),
-            Timeframe::Date(DateKind::Absolute(time)) => *time,
+            Timeframe::Date(DateKind::Today) => Resolved {
+                date: Utc::now(),
+                reference: None,
+            },
+            Timeframe::Date(DateKind::Absolute(time)) => Resolved {
+                date: *time,
+                reference: None,
+            },
+            Timeframe::Reference(reference) => Resolved {
+                date: forge.reference_date(&owner, &repo, reference).await?,
+                reference: Some(reference.clone()),
+            },
         })
     }
 }
 
+/// The latest release of `owner/repo`, or `None` if it has no releases and
+/// `config` falls back to the start of the repository.
+async fn latest_release(
+    forge: &dyn Forge,
+    config: &Config,
+    owner: &str,
+    repo: &str,
+) -> eyre::Result<Option<Release>> {
+    match forge.latest_release(owner, repo).await {
+        Ok(release) => Ok(Some(release)),
+        Err(error)
+            if config.on_missing_release == OnMissingRelease::RepoStart
+                && matches!(error.downcast_ref(), Some(Error::ReleaseNotFound(_))) =>
+        {
+            log::warn!(
+                "`{}/{}` has no releases, starting from the beginning of the repository.",
+                owner,
+                repo
+            );
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The release `number` releases before the latest one.
+async fn nth_latest_release(
+    forge: &dyn Forge,
+    owner: &str,
+    repo: &str,
+    number: u8,
+) -> eyre::Result<Release> {
+    let mut releases = forge.releases(owner, repo).await?;
+
+    releases.sort_by_key(|release| std::cmp::Reverse(release.created_at));
+
+    let count = releases.len();
+    releases.into_iter().nth(number as usize).ok_or_else(|| {
+        Error::ReleaseNotFound(format!(
+            "Expected at least {} releases, but only {} found.",
+            number + 1,
+            count
+        ))
+        .into()
+    })
+}
+
 impl std::str::FromStr for Timeframe {
     type Err = eyre::Report;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         static REGEX: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
-            regex::Regex::new(r"release:(?:(?:latest(-\d+)?)|(\S+))").unwrap()
+            regex::Regex::new(r"^release:(?:latest(?:-(\d+))?|(\S+))$").unwrap()
         });
 
         if let Ok(datetime) = s.parse() {
@@ -98,27 +160,35 @@ impl std::str::FromStr for Timeframe {
                 Date::from_utc(date, Utc).and_hms(0, 0, 0),
             )))
         } else if let Some(c) = REGEX.captures(s) {
-            Ok(if s.starts_with("release:latest") {
-                if let Some(number) = c
-                    .get(1)
-                    .and_then(|c| c.as_str().parse::<isize>().ok())
-                    .map(|n| n.abs() as u8)
-                {
-                    Timeframe::Release(ReleaseKind::RelativeFromLast(number))
-                } else {
-                    Timeframe::Release(ReleaseKind::Latest)
-                }
-            } else if let Some(tag) = c.get(1).map(|c| c.as_str().to_owned()) {
-                Timeframe::Release(ReleaseKind::Absolute(tag))
-            } else {
-                unreachable!()
-            })
+            match (c.get(1), c.get(2)) {
+                (_, Some(tag)) => Ok(Timeframe::Release(ReleaseKind::Absolute(
+                    tag.as_str().to_owned(),
+                ))),
+                (Some(number), None) => match number.as_str().parse() {
+                    Ok(number) => Ok(Timeframe::Release(ReleaseKind::RelativeFromLast(number))),
+                    Err(_) => Err(Error::InvalidTimeframe(format!(
+                        "`{}` must be at most {} releases before the latest.",
+                        s,
+                        u8::MAX
+                    ))
+                    .into()),
+                },
+                (None, None) => Ok(Timeframe::Release(ReleaseKind::Latest)),
+            }
+        } else if let Some(reference) = s
+            .strip_prefix("tag:")
+            .or_else(|| s.strip_prefix("ref:"))
+            .filter(|reference| !reference.is_empty())
+        {
+            Ok(Timeframe::Reference(reference.to_owned()))
         } else if s == "today" {
             Ok(Timeframe::Date(DateKind::Today))
         } else {
-            Err(eyre::eyre!(
-                "Timeframe must be a date or relative to the last release.",
+            Err(Error::InvalidTimeframe(format!(
+                "`{}` must be a date, a git reference, or relative to the last release.",
+                s
             ))
+            .into())
         }
     }
 }
@@ -135,3 +205,70 @@ impl<'de> serde::Deserialize<'de> for Timeframe {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolves_dates() {
+        let forge = crate::forge::fixtures::github("github");
+        let config = Config::new(String::from("EmbarkStudios"), String::from("relnotes"));
+        let date = |timeframe: &str| {
+            let timeframe = timeframe.parse::<Timeframe>().unwrap();
+            let (forge, config) = (&forge, &config);
+            async move {
+                let resolved = timeframe.resolve(forge, config).await.unwrap();
+                (resolved.date.to_rfc3339(), resolved.reference)
+            }
+        };
+
+        let resolved =
+            |date: &str, reference: Option<&str>| (date.to_owned(), reference.map(String::from));
+
+        assert_eq!(
+            date("release:latest").await,
+            resolved("2021-01-01T00:00:00+00:00", Some("v0.1.0"))
+        );
+        assert_eq!(
+            date("release:latest-1").await,
+            resolved("2020-06-01T00:00:00+00:00", Some("v0.0.1"))
+        );
+        assert_eq!(
+            date("release:v0.0.1").await,
+            resolved("2020-06-01T00:00:00+00:00", Some("v0.0.1"))
+        );
+        assert_eq!(
+            date("tag:main").await,
+            resolved("2021-01-20T12:00:00+00:00", Some("main"))
+        );
+        assert_eq!(
+            date("2021-03-01").await,
+            resolved("2021-03-01T00:00:00+00:00", None)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_releases_are_errors() {
+        let forge = crate::forge::fixtures::github("github");
+        let config = Config::new(String::from("EmbarkStudios"), String::from("relnotes"));
+
+        for timeframe in ["release:v9.9.9", "release:latest-2"] {
+            let error = timeframe
+                .parse::<Timeframe>()
+                .unwrap()
+                .resolve(&forge, &config)
+                .await
+                .unwrap_err();
+            assert_eq!(crate::error::exit_code(&error), 4, "{}", timeframe);
+        }
+        for timeframe in ["yesterday", "nightly-release:latest", "release:latest-300"] {
+            assert_eq!(
+                crate::error::exit_code(&timeframe.parse::<Timeframe>().unwrap_err()),
+                3,
+                "{}",
+                timeframe
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use semver::Version;
+use serde::Deserialize;
+
+use crate::forge::ChangeEntry;
+
+fn default_minor_types() -> RegexSet {
+    RegexSet::new(["^feat$"]).unwrap()
+}
+
+/// How much a change requires the version to be bumped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// How to compute the next version when it isn't given. Breaking changes
+/// always bump the major version, every other change bumps the patch version
+/// unless it matches one of these rules.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct VersionBump {
+    /// PRs with labels matching these bump the major version.
+    #[serde(deserialize_with = "super::from_regex_set")]
+    #[serde(default = "super::default_regex_set")]
+    pub major_labels: RegexSet,
+    /// PRs with conventional commit types matching these bump the major
+    /// version.
+    #[serde(deserialize_with = "super::from_regex_set")]
+    #[serde(default = "super::default_regex_set")]
+    pub major_types: RegexSet,
+    /// PRs with labels matching these bump the minor version.
+    #[serde(deserialize_with = "super::from_regex_set")]
+    #[serde(default = "super::default_regex_set")]
+    pub minor_labels: RegexSet,
+    /// PRs with conventional commit types matching these bump the minor
+    /// version.
+    #[serde(deserialize_with = "super::from_regex_set")]
+    #[serde(default = "default_minor_types")]
+    pub minor_types: RegexSet,
+}
+
+impl Default for VersionBump {
+    fn default() -> Self {
+        Self {
+            major_labels: super::default_regex_set(),
+            major_types: super::default_regex_set(),
+            minor_labels: super::default_regex_set(),
+            minor_types: default_minor_types(),
+        }
+    }
+}
+
+impl VersionBump {
+    /// The bump required by `change`.
+    pub fn bump(&self, change: &ChangeEntry) -> Bump {
+        let matches = |labels: &RegexSet, types: &RegexSet| {
+            change.labels.iter().any(|l| labels.is_match(&l.name))
+                || change
+                    .conventional
+                    .as_ref()
+                    .is_some_and(|conventional| types.is_match(&conventional.kind))
+        };
+
+        if change.breaking || matches(&self.major_labels, &self.major_types) {
+            Bump::Major
+        } else if matches(&self.minor_labels, &self.minor_types) {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+
+    /// Computes the version following the release tagged `previous` that
+    /// contains `changes`. Versions before `1.0.0` bump the minor version
+    /// for breaking changes, as is the convention for Rust crates.
+    pub fn next_version<'a>(
+        &self,
+        previous: &str,
+        changes: impl IntoIterator<Item = &'a ChangeEntry>,
+    ) -> eyre::Result<Version> {
+        let previous = version_from_tag(previous)?;
+        let bump = changes
+            .into_iter()
+            .map(|change| self.bump(change))
+            .max()
+            .unwrap_or(Bump::Patch);
+
+        Ok(match bump {
+            Bump::Major if previous.major > 0 => Version::new(previous.major + 1, 0, 0),
+            Bump::Major | Bump::Minor => Version::new(previous.major, previous.minor + 1, 0),
+            Bump::Patch => Version::new(previous.major, previous.minor, previous.patch + 1),
+        })
+    }
+}
+
+/// Parses the version at the end of `tag`, ignoring prefixes such as `v` or
+/// `my-crate-v`.
+fn version_from_tag(tag: &str) -> eyre::Result<Version> {
+    static VERSION: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\d+\.\d+\.\d+(?:[-+][0-9A-Za-z.+-]*)?$").unwrap());
+
+    let version = VERSION
+        .find(tag)
+        .ok_or_else(|| eyre::eyre!("Tag `{}` doesn't end with a semantic version.", tag))?;
+
+    Ok(Version::parse(version.as_str())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::change;
+
+    fn next(previous: &str, changes: &[ChangeEntry]) -> String {
+        VersionBump::default()
+            .next_version(previous, changes)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!(version_from_tag("v1.2.3").unwrap(), Version::new(1, 2, 3));
+        assert_eq!(
+            version_from_tag("relnotes-v0.4.0").unwrap(),
+            Version::new(0, 4, 0)
+        );
+        assert!(version_from_tag("latest").is_err());
+    }
+
+    #[test]
+    fn bumps_by_largest_change() {
+        let fix = change("fix: Crash on start up", "", &[]);
+        let feat = change("feat: Add `--output`", "", &[]);
+        let mut breaking = change("Rename `run`", "", &[]);
+        breaking.breaking = true;
+
+        assert_eq!(next("v1.2.3", &[]), "1.2.4");
+        assert_eq!(next("v1.2.3", std::slice::from_ref(&fix)), "1.2.4");
+        assert_eq!(next("v1.2.3", &[fix.clone(), feat.clone()]), "1.3.0");
+        assert_eq!(next("v1.2.3", &[fix, feat, breaking.clone()]), "2.0.0");
+        assert_eq!(next("v0.2.3", &[breaking]), "0.3.0");
+    }
+
+    #[test]
+    fn bumps_by_labels() {
+        let rules = VersionBump {
+            minor_labels: RegexSet::new(["^enhancement$"]).unwrap(),
+            ..VersionBump::default()
+        };
+
+        assert_eq!(
+            rules.bump(&change("Add `--output`", "", &["enhancement"])),
+            Bump::Minor
+        );
+        assert_eq!(rules.bump(&change("Add `--output`", "", &[])), Bump::Patch);
+    }
+}
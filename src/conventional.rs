@@ -0,0 +1,84 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// A title following the [Conventional Commits][spec] specification, e.g.
+/// `feat(render)!: Remove the old pipeline`.
+///
+/// [spec]: https://www.conventionalcommits.org/en/v1.0.0/
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ConventionalCommit {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl ConventionalCommit {
+    /// Parses `title`, returning `None` if it isn't a conventional commit.
+    pub fn parse(title: &str) -> Option<Self> {
+        static REGEX: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^(?P<type>[[:alnum:]-]+)(?:\((?P<scope>[^()]*)\))?(?P<breaking>!)?: +(?P<description>.+)$",
+            )
+            .unwrap()
+        });
+
+        let captures = REGEX.captures(title.trim())?;
+
+        Some(Self {
+            kind: captures["type"].to_owned(),
+            scope: captures
+                .name("scope")
+                .map(|scope| scope.as_str().trim().to_owned())
+                .filter(|scope| !scope.is_empty()),
+            breaking: captures.name("breaking").is_some(),
+            description: captures["description"].trim().to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_type_and_description() {
+        assert_eq!(
+            ConventionalCommit::parse("fix: Crash on start up"),
+            Some(ConventionalCommit {
+                kind: String::from("fix"),
+                scope: None,
+                breaking: false,
+                description: String::from("Crash on start up"),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_scope_and_breaking() {
+        assert_eq!(
+            ConventionalCommit::parse("feat(render)!: Remove the old pipeline"),
+            Some(ConventionalCommit {
+                kind: String::from("feat"),
+                scope: Some(String::from("render")),
+                breaking: true,
+                description: String::from("Remove the old pipeline"),
+            })
+        );
+        assert!(
+            ConventionalCommit::parse("fix!: Rename `run`")
+                .unwrap()
+                .breaking
+        );
+    }
+
+    #[test]
+    fn ignores_other_titles() {
+        assert_eq!(ConventionalCommit::parse("Update spirv-tools"), None);
+        assert_eq!(
+            ConventionalCommit::parse("Update winit: v0.23 -> v0.24"),
+            None
+        );
+    }
+}
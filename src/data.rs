@@ -1,42 +1,67 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::BTreeSet,
+    path::{Component, Path, PathBuf},
+};
+
+use futures::{StreamExt, TryStreamExt};
+use once_cell::sync::Lazy;
+use regex::Regex;
 
-use octocrab::{
-    models::{pulls::PullRequest, User},
-    Octocrab,
+use crate::{
+    config::{ReleaseNote, Timeframe, DATE_FORMAT},
+    conventional::ConventionalCommit,
+    error::Error,
+    forge::{ChangeEntry, Forge, Range, User},
 };
 
-use crate::config::DATE_FORMAT;
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Category {
+    title: String,
+    description: Option<String>,
+    prs: Vec<ChangeEntry>,
+}
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct Data {
-    categories: HashMap<String, Vec<PullRequest>>,
-    contributors: HashSet<User>,
+    breaking: Vec<ChangeEntry>,
+    categories: Vec<Category>,
+    contributors: BTreeSet<User>,
     date: String,
     includes: Vec<Data>,
     owner: String,
-    prs: Vec<PullRequest>,
+    prs: Vec<ChangeEntry>,
     repo: String,
     title: String,
     version: String,
 }
 
 impl Data {
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
     #[async_recursion::async_recursion]
     pub async fn from_config(
-        octocrab: &Octocrab,
-        version: String,
+        forge: &dyn Forge,
+        version: Option<String>,
         config: &crate::config::Config,
     ) -> eyre::Result<Self> {
         log::debug!("Config: {:#?}", &config);
 
-        let from_date = config.from.date_from_timeframe(octocrab, config).await?;
-        let to_date = config.to.date_from_timeframe(octocrab, config).await?;
+        let from = config.from.resolve(forge, config).await?;
+        let to = config.to.resolve(forge, config).await?;
+        let (from_date, to_date) = (from.date, to.date);
 
         if from_date > to_date {
-            panic!(
-                "`to` ({}) date is earlier than `from` ({}) date.",
-                from_date, to_date
-            );
+            return Err(Error::InvalidTimeframe(format!(
+                "`to` ({}) is earlier than `from` ({}).",
+                to_date, from_date
+            ))
+            .into());
         }
 
         log::info!(
@@ -47,74 +72,97 @@ impl Data {
             to = to_date.format(DATE_FORMAT),
         );
 
-        let repo = format!("{}/{}", config.owner, config.repo);
-        let date_range = format!(
-            "{}..{}",
-            from_date.format(DATE_FORMAT),
-            to_date.format(DATE_FORMAT)
-        );
-        let query_string = format!("repo:{} is:pr is:merged merged:{}", repo, date_range);
-        let page = octocrab
-            .search()
-            .issues_and_pull_requests(&query_string)
-            .per_page(100u8)
-            .send()
+        // References from the parent repository don't exist in this one.
+        let (from_reference, to_reference) = if config.parent.is_none() {
+            (from.reference.clone(), to.reference)
+        } else {
+            (None, None)
+        };
+        let range = Range {
+            from: from_date,
+            to: to_date,
+            from_reference,
+            to_reference,
+        };
+        let changes = forge
+            .merged_changes(&config.owner, &config.repo, &range)
             .await?;
 
-        let mut issues = page.items;
-        let mut next = page.next;
-        while let Ok(Some(mut page)) = octocrab.get_page(&next).await {
-            issues.append(&mut page.items);
-            next = page.next;
-        }
-
         let mut pulls = Vec::new();
-        let mut categories: HashMap<_, Vec<_>> = HashMap::new();
-        let mut contributors = HashSet::new();
+        let mut categories = config
+            .categories
+            .iter()
+            .map(|category| Category {
+                title: category.title.clone(),
+                description: category.description.clone(),
+                prs: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let mut contributors = BTreeSet::new();
+        let mut breaking = Vec::new();
 
-        'issues: for issue in issues {
-            if issue
-                .labels
-                .iter()
-                .any(|l| config.skip_labels.is_match(&l.name))
-            {
-                continue;
+        // Fetching the details of each change is the slowest part, so they're
+        // fetched concurrently. `buffered` keeps them in order.
+        let changes = futures::stream::iter(changes)
+            .map(|change| Self::fetch_change(forge, config, change))
+            .buffered(config.jobs.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        'changes: for change in changes.into_iter().flatten() {
+            if change.breaking {
+                breaking.push(change.clone());
             }
+            contributors.insert(change.user.clone());
 
-            for category in &config.categories {
-                if issue
-                    .labels
-                    .iter()
-                    .any(|l| category.labels.is_match(&l.name))
-                {
-                    let body = octocrab
-                        ._get(issue.pull_request.unwrap().url.clone(), None::<&()>)
-                        .await?
-                        .text()
-                        .await?;
-                    categories
-                        .entry(category.title.clone())
-                        .or_default()
-                        .push(serde_json::from_str(&body)?);
-                    continue 'issues;
+            for (index, category) in config.categories.iter().enumerate() {
+                if category.matches(&change) {
+                    categories[index].prs.push(change);
+                    continue 'changes;
                 }
             }
 
-            contributors.insert(issue.user.clone());
-
-            let body = octocrab
-                ._get(issue.pull_request.unwrap().url.clone(), None::<&()>)
-                .await?
-                .text()
-                .await?;
-            pulls.push(serde_json::from_str(&body)?);
+            pulls.push(change);
         }
 
-        let mut includes = Vec::new();
-        for include in config.includes() {
-            let config = Self::from_config(octocrab, version.clone(), &include).await?;
-            contributors.extend(config.contributors.clone().into_iter());
-            includes.push(config);
+        let categories = sort_categories(&config.categories, categories);
+
+        let version = match version {
+            Some(version) => version,
+            None => {
+                let previous = from.reference.ok_or_else(|| {
+                    Error::Config(String::from(
+                        "A version is required when there is no previous release to bump, \
+                         e.g. when `from` is a date.",
+                    ))
+                })?;
+                let version = config
+                    .version_bump
+                    .next_version(
+                        &previous,
+                        pulls
+                            .iter()
+                            .chain(categories.iter().flat_map(|category| &category.prs)),
+                    )?
+                    .to_string();
+                log::info!(
+                    "Computed `{}` as version number from `{}`.",
+                    version,
+                    previous
+                );
+                version
+            }
+        };
+
+        let includes = config.includes();
+        let includes = futures::future::try_join_all(
+            includes
+                .iter()
+                .map(|include| Self::from_config(forge, Some(version.clone()), include)),
+        )
+        .await?;
+        for include in &includes {
+            contributors.extend(include.contributors.iter().cloned());
         }
 
         Ok(Self {
@@ -123,10 +171,447 @@ impl Data {
             repo: config.repo.clone(),
             title: config.title.clone().unwrap_or_else(|| config.repo.clone()),
             date: to_date.format(&config.date_format).to_string(),
+            breaking,
             categories,
             includes,
             prs: pulls,
             contributors,
         })
     }
+
+    /// Fetches the details of `change`, or `None` if it is skipped.
+    async fn fetch_change(
+        forge: &dyn Forge,
+        config: &crate::config::Config,
+        mut change: ChangeEntry,
+    ) -> eyre::Result<Option<ChangeEntry>> {
+        change.conventional = ConventionalCommit::parse(&change.title);
+
+        if change
+            .labels
+            .iter()
+            .any(|l| config.skip_labels.is_match(&l.name))
+        {
+            return Ok(None);
+        }
+
+        let mut change = forge
+            .change_details(&config.owner, &config.repo, change)
+            .await?;
+        change.files = forge
+            .changed_files(&config.owner, &config.repo, &change)
+            .await?;
+
+        if !config.is_in_scope(&change) {
+            return Ok(None);
+        }
+
+        // Some forges already know the linked issues.
+        for issue in change
+            .body
+            .as_deref()
+            .map(linked_issues)
+            .unwrap_or_default()
+        {
+            if !change.linked_issues.contains(&issue) {
+                change.linked_issues.push(issue);
+            }
+        }
+
+        match change
+            .body
+            .as_deref()
+            .and_then(|body| config.release_notes.extract(body))
+        {
+            Some(ReleaseNote::Note(note)) => change.release_note = Some(note),
+            Some(ReleaseNote::None) if config.release_notes.skip_none => return Ok(None),
+            Some(ReleaseNote::None) | None => {}
+        }
+
+        let commits = if config.breaking_changes.commits {
+            forge
+                .commit_messages(&config.owner, &config.repo, &change)
+                .await?
+        } else {
+            Vec::new()
+        };
+        let (is_breaking, breaking_note) = config.breaking_changes.detect(&change, &commits);
+        change.breaking = is_breaking;
+        change.breaking_note = breaking_note;
+
+        Ok(Some(change))
+    }
+
+    /// Generates the release notes of each member of the Cargo workspace
+    /// whose manifest is at `manifest`, since the member's latest
+    /// `<crate>-v<version>` tag, along with the member's directory.
+    /// Members without any changes are skipped.
+    pub async fn from_workspace(
+        forge: &dyn Forge,
+        config: &crate::config::Config,
+        manifest: &Path,
+    ) -> eyre::Result<Vec<(PathBuf, Self)>> {
+        let root = repository_path(manifest.parent().unwrap_or_else(|| Path::new("")))?;
+        let members = crate::cargo::members(manifest)?;
+        let directories = members
+            .iter()
+            .map(|member| root.join(&member.path))
+            .collect::<Vec<_>>();
+        // Crates are often tagged without publishing a release on the forge.
+        let tags = forge.tags(&config.owner, &config.repo).await?;
+
+        let mut notes = Vec::new();
+        for (member, directory) in members.iter().zip(&directories) {
+            let prefix = format!("{}-v", member.name);
+            let latest = tags
+                .iter()
+                .filter_map(|tag| {
+                    let version = semver::Version::parse(tag.strip_prefix(&prefix)?).ok()?;
+                    Some((version, tag))
+                })
+                .max();
+
+            let (from, version) = match latest {
+                // The manifest still has the released version, so compute
+                // the next one.
+                Some((_, tag)) if tag[prefix.len()..] == member.version => {
+                    (Timeframe::Reference(tag.clone()), None)
+                }
+                Some((_, tag)) => (
+                    Timeframe::Reference(tag.clone()),
+                    Some(member.version.clone()),
+                ),
+                None => {
+                    log::warn!(
+                        "`{}` has no `{}<version>` tag, using `from` instead.",
+                        member.name,
+                        prefix
+                    );
+                    (config.from.clone(), Some(member.version.clone()))
+                }
+            };
+
+            let others = directories
+                .iter()
+                .filter(|other| *other != directory)
+                .cloned()
+                .collect::<Vec<_>>();
+            let config = config.for_package(&member.name, directory, &others, from)?;
+            let data = Self::from_config(forge, version, &config).await?;
+
+            if data.prs.is_empty() && data.categories.is_empty() {
+                log::info!("`{}` has no changes, skipping.", member.name);
+                continue;
+            }
+
+            notes.push((directory.clone(), data));
+        }
+
+        Ok(notes)
+    }
+}
+
+/// `path` relative to the root of the repository, which is the current
+/// directory, as the files changes touch are.
+fn repository_path(path: &Path) -> eyre::Result<PathBuf> {
+    let outside = || {
+        Error::Config(format!(
+            "`{}` is outside of the repository, run `relnotes` from the root of the \
+             repository.",
+            path.display()
+        ))
+    };
+    let current_dir = std::env::current_dir()?;
+    let relative = match path.strip_prefix(&current_dir) {
+        Ok(relative) => relative,
+        Err(_) if path.is_absolute() => return Err(outside().into()),
+        Err(_) => path,
+    };
+
+    relative
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(outside().into()),
+        })
+        .collect()
+}
+
+/// The issues `body` closes with GitHub's and GitLab's closing keywords,
+/// e.g. `Fixes #123` or `Closes #1, #2 and #3`.
+fn linked_issues(body: &str) -> Vec<u64> {
+    static KEYWORDS: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)\b(?:close[sd]?|closing|fix(?:e[sd]|ing)?|resolv(?:e[sd]?|ing)|implement(?:s|ed|ing)?):?\s+#\d+(?:(?:\s*,\s*|\s+and\s+|\s*,\s*and\s+)#\d+)*",
+        )
+        .unwrap()
+    });
+    static ISSUE: Lazy<Regex> = Lazy::new(|| Regex::new(r"#(\d+)").unwrap());
+
+    let mut issues = Vec::new();
+    for keywords in KEYWORDS.find_iter(body) {
+        for captures in ISSUE.captures_iter(keywords.as_str()) {
+            if let Ok(issue) = captures[1].parse() {
+                if !issues.contains(&issue) {
+                    issues.push(issue);
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Removes the empty categories and sorts the rest by the `order` of their
+/// configuration in `configured`.
+fn sort_categories(
+    configured: &[crate::config::Category],
+    categories: Vec<Category>,
+) -> Vec<Category> {
+    // `sort_by_key` is stable, so categories with the same `order` stay in
+    // the order they were configured.
+    let mut categories = configured
+        .iter()
+        .zip(categories)
+        .filter(|(_, category)| !category.prs.is_empty())
+        .collect::<Vec<_>>();
+    categories.sort_by_key(|(category, _)| category.order);
+
+    categories
+        .into_iter()
+        .map(|(_, category)| category)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_linked_issues() {
+        assert_eq!(
+            linked_issues("Fixes #12.\n\nCloses #3, #4 and #5, resolves: #12\nSee #6"),
+            [12, 3, 4, 5]
+        );
+        assert!(linked_issues("Prefixes #1, fix for #2").is_empty());
+    }
+
+    #[test]
+    fn sorts_categories_by_order() {
+        #[derive(serde::Deserialize)]
+        struct Categories {
+            categories: Vec<crate::config::Category>,
+        }
+
+        let configured = toml::from_str::<Categories>(
+            r#"
+            [[categories]]
+            title = "Later"
+            order = 2
+
+            [[categories]]
+            title = "Unordered"
+
+            [[categories]]
+            title = "Empty"
+            order = -2
+
+            [[categories]]
+            title = "First tie"
+            order = 1
+
+            [[categories]]
+            title = "Second tie"
+            order = 1
+
+            [[categories]]
+            title = "Earliest"
+            order = -1
+            "#,
+        )
+        .unwrap()
+        .categories;
+        let categories = configured
+            .iter()
+            .map(|category| Category {
+                title: category.title.clone(),
+                description: None,
+                prs: if category.title == "Empty" {
+                    Vec::new()
+                } else {
+                    vec![ChangeEntry::default()]
+                },
+            })
+            .collect();
+
+        assert_eq!(
+            sort_categories(&configured, categories)
+                .iter()
+                .map(|category| category.title.as_str())
+                .collect::<Vec<_>>(),
+            ["Earliest", "Unordered", "First tie", "Second tie", "Later"]
+        );
+    }
+
+    #[test]
+    fn renders_snapshot() {
+        let data: Data = serde_json::from_str(
+            r#"{
+                "breaking": [],
+                "categories": [],
+                "contributors": [{ "login": "octocat", "html_url": "https://github.com/octocat" }],
+                "date": "2021-01-01",
+                "includes": [],
+                "owner": "EmbarkStudios",
+                "prs": [{
+                    "number": 1,
+                    "title": "Fix crash",
+                    "html_url": "https://github.com/EmbarkStudios/relnotes/pull/1",
+                    "user": { "login": "octocat", "html_url": "https://github.com/octocat" },
+                    "labels": [],
+                    "body": null,
+                    "merged_at": "2021-01-01T00:00:00Z",
+                    "merge_commit_sha": null,
+                    "conventional": null,
+                    "files": [],
+                    "release_note": null,
+                    "breaking": false,
+                    "breaking_note": null
+                }],
+                "repo": "relnotes",
+                "title": "relnotes",
+                "version": "0.1.0"
+            }"#,
+        )
+        .unwrap();
+
+        let rendered = tera::Tera::one_off(
+            &crate::config::Template::default(),
+            &tera::Context::from_serialize(data).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        assert!(rendered.starts_with("# relnotes 0.1.0 (2021-01-01)\n"));
+        assert!(
+            rendered.contains("- [Fix crash](https://github.com/EmbarkStudios/relnotes/pull/1)")
+        );
+        assert!(rendered.contains("- [octocat](https://github.com/octocat)"));
+    }
+
+    #[tokio::test]
+    async fn renders_recorded_release() {
+        let forge = crate::forge::fixtures::github("github");
+        let mut config =
+            crate::config::Config::new(String::from("EmbarkStudios"), String::from("relnotes"));
+        config.to = "2021-02-01T00:00:00Z".parse().unwrap();
+
+        let data = Data::from_config(&forge, None, &config).await.unwrap();
+        assert_eq!(data.version(), "0.2.0");
+        assert_eq!(
+            data.prs.iter().map(|pr| pr.number).collect::<Vec<_>>(),
+            [3, 1]
+        );
+        assert_eq!(data.breaking.len(), 1);
+
+        let rendered = crate::render(crate::Format::Template, &config.template, data).unwrap();
+        assert!(rendered.starts_with("# relnotes 0.2.0 (2021-02-01)\n"));
+        assert!(rendered.contains(
+            "- [feat!: Rename `--from`](https://github.com/EmbarkStudios/relnotes/pull/3)"
+        ));
+        assert!(rendered.contains("- [octocat](https://github.com/octocat)"));
+    }
+
+    #[tokio::test]
+    async fn credits_the_authors_of_categorised_changes() {
+        let forge = crate::forge::fixtures::github("github");
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            owner = "EmbarkStudios"
+            repo = "relnotes"
+            to = "2021-02-01T00:00:00Z"
+
+            [template]
+            string = ""
+
+            [[categories]]
+            title = "Changes"
+            types = ["feat", "fix"]
+            "#,
+        )
+        .unwrap();
+
+        let data = Data::from_config(&forge, None, &config).await.unwrap();
+        assert!(data.prs.is_empty());
+        assert_eq!(data.categories[0].prs.len(), 2);
+        assert_eq!(
+            data.contributors
+                .iter()
+                .map(|user| user.login.as_str())
+                .collect::<Vec<_>>(),
+            ["octocat"]
+        );
+    }
+
+    #[tokio::test]
+    async fn starts_workspace_members_from_tags_without_releases() {
+        let forge = crate::forge::fixtures::github("workspace");
+        let mut config =
+            crate::config::Config::new(String::from("EmbarkStudios"), String::from("crates"));
+        config.to = "2021-02-01T00:00:00Z".parse().unwrap();
+
+        let manifest = Path::new("tests")
+            .join("fixtures")
+            .join("workspace")
+            .join("Cargo.toml");
+
+        // Absolute paths are relative to the repository, the current
+        // directory.
+        for manifest in [
+            manifest.clone(),
+            std::env::current_dir().unwrap().join(manifest),
+        ] {
+            let notes = Data::from_workspace(&forge, &config, &manifest)
+                .await
+                .unwrap();
+            assert_eq!(notes.len(), 1);
+            let (directory, data) = &notes[0];
+            assert_eq!(
+                directory,
+                &Path::new("tests/fixtures/workspace")
+                    .join("crates")
+                    .join("render")
+            );
+            assert_eq!(data.title, "render");
+            // `render-v0.1.0` is the manifest's version, so the next one is
+            // computed from it.
+            assert_eq!(data.version(), "0.2.0");
+            assert_eq!(data.prs.iter().map(|pr| pr.number).collect::<Vec<_>>(), [5]);
+        }
+
+        let outside = Data::from_workspace(&forge, &config, Path::new("../Cargo.toml"))
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::exit_code(&outside), 2);
+    }
+
+    #[tokio::test]
+    async fn starts_from_the_repository_without_releases() {
+        let forge = crate::forge::fixtures::github("first-release");
+        let mut config =
+            crate::config::Config::new(String::from("EmbarkStudios"), String::from("new-crate"));
+        config.to = "2021-02-01T00:00:00Z".parse().unwrap();
+
+        let data = Data::from_config(&forge, Some(String::from("0.1.0")), &config)
+            .await
+            .unwrap();
+        assert_eq!(data.prs.iter().map(|pr| pr.number).collect::<Vec<_>>(), [1]);
+
+        config.on_missing_release = crate::config::OnMissingRelease::Error;
+        let error = Data::from_config(&forge, Some(String::from("0.1.0")), &config)
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::exit_code(&error), 4);
+    }
 }
@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// The errors callers of `relnotes` might want to handle differently, each
+/// with its own exit code. Other errors exit with `1`.
+#[derive(Debug)]
+pub enum Error {
+    /// The configuration file or command line arguments are invalid.
+    Config(String),
+    /// The timeframe is invalid, e.g. `from` is after `to`.
+    InvalidTimeframe(String),
+    /// A release `from` or `to` refers to doesn't exist.
+    ReleaseNotFound(String),
+    /// The forge rejected the token, or requires one.
+    Authentication(String),
+    /// The forge's rate limit was exceeded, and didn't reset in time.
+    RateLimited(String),
+    /// The template couldn't be rendered.
+    Template(tera::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 2,
+            Self::InvalidTimeframe(_) => 3,
+            Self::ReleaseNotFound(_) => 4,
+            Self::Authentication(_) => 5,
+            Self::RateLimited(_) => 6,
+            Self::Template(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(message) => write!(f, "Invalid configuration: {}", message),
+            Self::InvalidTimeframe(message) => write!(f, "Invalid timeframe: {}", message),
+            Self::ReleaseNotFound(message) => write!(
+                f,
+                "{} Set `from` to a date or `tag:<tag>` to generate release notes without a \
+                 previous release.",
+                message
+            ),
+            Self::Authentication(message) => write!(
+                f,
+                "{} Check that the token in `--token`, `GITHUB_TOKEN`, or `GITLAB_TOKEN` is \
+                 valid and hasn't expired.",
+                message
+            ),
+            Self::RateLimited(message) => write!(
+                f,
+                "{} Try again later, or use a token to get a higher rate limit.",
+                message
+            ),
+            Self::Template(_) => write!(f, "Couldn't render the template."),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Template(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The exit code for `report`, from the first `Error` in its chain.
+pub fn exit_code(report: &eyre::Report) -> i32 {
+    report
+        .chain()
+        .find_map(|error| error.downcast_ref::<Error>())
+        .map_or(1, Error::exit_code)
+}
@@ -0,0 +1,224 @@
+pub mod fixtures;
+pub mod github;
+pub mod gitlab;
+mod http;
+pub mod local_git;
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use self::{
+    fixtures::Fixtures,
+    http::{Cache, Http},
+};
+use crate::{
+    config::{Config, Provider},
+    conventional::ConventionalCommit,
+};
+
+pub use github::GitHub;
+pub use gitlab::GitLab;
+pub use local_git::LocalGit;
+
+/// A user on a forge, either the author of a change or a contributor to the
+/// release.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct User {
+    pub login: String,
+    pub html_url: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Label {
+    pub name: String,
+}
+
+/// A change (pull request, merge request, etc.) that was merged into the
+/// repository. This is what templates and `--format json` see of a change
+/// on every forge, so fields are only ever added to it.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct ChangeEntry {
+    pub number: u64,
+    pub title: String,
+    pub html_url: String,
+    pub user: User,
+    pub labels: Vec<Label>,
+    pub body: Option<String>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub merge_commit_sha: Option<String>,
+    /// The change's title parsed as a conventional commit, if it is one.
+    pub conventional: Option<ConventionalCommit>,
+    /// The paths of the files the change touched.
+    pub files: Vec<String>,
+    /// The user facing release note from the change's body.
+    pub release_note: Option<String>,
+    /// Whether the change contains breaking changes.
+    pub breaking: bool,
+    /// The migration note from the change's `BREAKING CHANGE:` footers.
+    pub breaking_note: Option<String>,
+    /// The branch the change was merged into.
+    #[serde(default)]
+    pub base_branch: Option<String>,
+    /// The branch the change was merged from.
+    #[serde(default)]
+    pub head_branch: Option<String>,
+    /// The issues the change closes with keywords such as `Fixes #123` in
+    /// its body.
+    #[serde(default)]
+    pub linked_issues: Vec<u64>,
+    /// Information only some forges have, such as the change's milestone.
+    #[serde(default)]
+    pub extras: BTreeMap<String, serde_json::Value>,
+    /// The messages of the commits the change merged, if the forge listed
+    /// them along with the change. They are only used to detect breaking
+    /// changes, so they aren't part of the data.
+    #[serde(skip)]
+    pub commit_messages: Vec<String>,
+}
+
+/// A change titled `title`, with `body` (if it isn't empty) and `labels`.
+#[cfg(test)]
+pub fn change(title: &str, body: &str, labels: &[&str]) -> ChangeEntry {
+    ChangeEntry {
+        title: title.to_owned(),
+        labels: labels
+            .iter()
+            .map(|name| Label {
+                name: (*name).to_owned(),
+            })
+            .collect(),
+        body: Some(body.to_owned()).filter(|body| !body.is_empty()),
+        conventional: ConventionalCommit::parse(title),
+        ..Default::default()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    pub created_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
+}
+
+/// The span of history to collect changes from. `from_reference` and
+/// `to_reference` are the git references (tags, branches, commits) the span
+/// was resolved from, if any.
+#[derive(Clone, Debug)]
+pub struct Range {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub from_reference: Option<String>,
+    pub to_reference: Option<String>,
+}
+
+/// A source of changes and releases for a repository.
+#[async_trait::async_trait]
+pub trait Forge: Send + Sync {
+    /// Lists every change merged into `owner/repo` within `range`. The
+    /// changes returned may only be partially filled in, use
+    /// `change_details` to retrieve the rest of the change.
+    async fn merged_changes(
+        &self,
+        owner: &str,
+        repo: &str,
+        range: &Range,
+    ) -> eyre::Result<Vec<ChangeEntry>>;
+
+    /// Fills in any details of `change` that weren't available when listing
+    /// changes.
+    async fn change_details(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: ChangeEntry,
+    ) -> eyre::Result<ChangeEntry>;
+
+    /// Lists the paths of every file `change` touched.
+    async fn changed_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>>;
+
+    /// Lists the messages of every commit in `change`.
+    async fn commit_messages(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>>;
+
+    /// Returns the most recent release of `owner/repo`.
+    async fn latest_release(&self, owner: &str, repo: &str) -> eyre::Result<Release>;
+
+    /// Returns the release of `owner/repo` tagged with `tag`.
+    async fn release(&self, owner: &str, repo: &str, tag: &str) -> eyre::Result<Release>;
+
+    /// Lists every release of `owner/repo`.
+    async fn releases(&self, owner: &str, repo: &str) -> eyre::Result<Vec<Release>>;
+
+    /// Lists the names of every tag of `owner/repo`, including those without
+    /// a release.
+    async fn tags(&self, owner: &str, repo: &str) -> eyre::Result<Vec<String>>;
+
+    /// Returns the date of the commit `reference` points to in `owner/repo`.
+    async fn reference_date(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> eyre::Result<DateTime<Utc>>;
+
+    /// Returns when `owner/repo` was created, or the date of its first
+    /// commit, for timeframes starting before the first release.
+    async fn repository_start(&self, owner: &str, repo: &str) -> eyre::Result<DateTime<Utc>>;
+}
+
+/// Creates the forge for `config`'s provider, authenticated with `token` if
+/// provided, caching its responses in `cache_dir`, or the user's cache
+/// directory by default. Its requests are recorded to the file in
+/// `RELNOTES_RECORD`, or replayed from the file in `RELNOTES_REPLAY`.
+pub fn from_config(
+    config: &Config,
+    token: Option<String>,
+    cache_dir: Option<PathBuf>,
+) -> eyre::Result<Box<dyn Forge>> {
+    let url = config.provider_url.clone();
+    let http = if let Some(path) = std::env::var_os("RELNOTES_REPLAY") {
+        Http::with_fixtures(Fixtures::replay(path.as_ref())?)
+    } else if let Some(path) = std::env::var_os("RELNOTES_RECORD") {
+        Http::with_fixtures(Fixtures::record(path.into()))
+    } else {
+        Http::new(cache_dir.or_else(Cache::default_directory).map(Cache::new))
+    };
+    let token_from_env = |name| std::env::var(name).ok().filter(|token| !token.is_empty());
+    Ok(match config.provider {
+        Provider::GitHub => Box::new(GitHub::new(
+            url,
+            token.or_else(|| token_from_env("GITHUB_TOKEN")),
+            http,
+        )?),
+        Provider::GitLab => Box::new(GitLab::new(
+            url,
+            token.or_else(|| token_from_env("GITLAB_TOKEN")),
+            http,
+        )?),
+        Provider::LocalGit => Box::new(LocalGit::new(
+            config.repository_path.clone().unwrap_or_else(|| ".".into()),
+            url,
+        )),
+    })
+}
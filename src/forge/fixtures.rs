@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use reqwest::{RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+
+use super::http::Retry;
+
+/// The response headers forges read, the rest aren't recorded to keep
+/// fixtures small.
+const RECORDED_HEADERS: &[&str] = &["link", "x-next-page"];
+
+/// Requests to forges and their responses, stored in a JSON file so tests
+/// and bug reports can be replayed without a network connection. Set
+/// `RELNOTES_RECORD` to a path to record them, and `RELNOTES_REPLAY` to
+/// replay them.
+#[derive(Debug)]
+pub enum Fixtures {
+    Record {
+        path: PathBuf,
+        exchanges: Mutex<Vec<Exchange>>,
+    },
+    Replay(Vec<Exchange>),
+}
+
+/// A request and the response it got.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Exchange {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    /// The body as JSON, or as a string if it isn't JSON.
+    body: serde_json::Value,
+}
+
+impl Fixtures {
+    pub fn record(path: PathBuf) -> Self {
+        Self::Record {
+            path,
+            exchanges: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn replay(path: &Path) -> eyre::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| eyre::eyre!("Couldn't read `{}`: {}", path.display(), error))?;
+
+        Ok(Self::Replay(serde_json::from_str(&contents)?))
+    }
+
+    /// Sends `request` with `retry` and records its response, or responds
+    /// with the recorded response to the same request.
+    pub async fn send(&self, retry: &Retry, request: RequestBuilder) -> eyre::Result<Response> {
+        let recorded_request = RecordedRequest::new(&request)?;
+
+        match self {
+            Self::Replay(exchanges) => exchanges
+                .iter()
+                .find(|exchange| exchange.request == recorded_request)
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "No fixture for `{} {}`.",
+                        recorded_request.method,
+                        recorded_request.url
+                    )
+                })?
+                .response
+                .response(),
+            Self::Record { path, exchanges } => {
+                let response = RecordedResponse::new(retry.send(request).await?).await?;
+                let reply = response.response();
+
+                let mut exchanges = exchanges.lock().unwrap();
+                if !exchanges
+                    .iter()
+                    .any(|exchange| exchange.request == recorded_request)
+                {
+                    exchanges.push(Exchange {
+                        request: recorded_request,
+                        response,
+                    });
+                    // Written after every request to keep what was recorded
+                    // if relnotes fails later on.
+                    std::fs::write(path, serde_json::to_string_pretty(&*exchanges)?)?;
+                }
+
+                reply
+            }
+        }
+    }
+}
+
+impl RecordedRequest {
+    fn new(request: &RequestBuilder) -> eyre::Result<Self> {
+        let request = request
+            .try_clone()
+            .ok_or_else(|| eyre::eyre!("Can't record a request with a streaming body."))?
+            .build()?;
+
+        Ok(Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(body_to_json),
+        })
+    }
+}
+
+impl RecordedResponse {
+    async fn new(response: Response) -> eyre::Result<Self> {
+        let headers = RECORDED_HEADERS
+            .iter()
+            .filter_map(|&name| {
+                let value = response.headers().get(name)?.to_str().ok()?;
+                Some((name.to_owned(), value.to_owned()))
+            })
+            .collect();
+
+        Ok(Self {
+            status: response.status().as_u16(),
+            headers,
+            body: body_to_json(&response.bytes().await?),
+        })
+    }
+
+    fn response(&self) -> eyre::Result<Response> {
+        let mut response = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+        let body = match &self.body {
+            serde_json::Value::String(body) => body.clone(),
+            body => body.to_string(),
+        };
+
+        Ok(response.body(body)?.into())
+    }
+}
+
+fn body_to_json(body: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned().into())
+}
+
+/// GitHub, replaying `tests/fixtures/<name>.json`.
+#[cfg(test)]
+pub fn github(name: &str) -> super::GitHub {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .with_extension("json");
+
+    super::GitHub::new(
+        None,
+        None,
+        super::http::Http::with_fixtures(Fixtures::replay(&path).unwrap()),
+    )
+    .unwrap()
+}
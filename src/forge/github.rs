@@ -0,0 +1,905 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use octocrab::{FromResponse, Octocrab, Page};
+use reqwest::{Method, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    http::{Http, NotFound},
+    ChangeEntry, Forge, Label, Range, Release, User,
+};
+
+/// The format of timestamps in search queries.
+const SEARCH_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+/// How many results the search API returns for a query at most.
+const SEARCH_LIMIT: u64 = 1000;
+const GITHUB_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// The pull requests matching a search query, with everything relnotes
+/// needs to know about them.
+const MERGED_PULL_REQUESTS: &str = "
+query($query: String!, $after: String) {
+  search(type: ISSUE, query: $query, first: 100, after: $after) {
+    issueCount
+    pageInfo { hasNextPage endCursor }
+    nodes {
+      ... on PullRequest {
+        number
+        title
+        url
+        body
+        mergedAt
+        author { login url }
+        labels(first: 100) { nodes { name } }
+        mergeCommit { oid }
+        baseRefName
+        headRefName
+        milestone { title }
+        authorAssociation
+        files(first: 100) { totalCount nodes { path } }
+        commits(first: 100) { totalCount nodes { commit { message } } }
+        closingIssuesReferences(first: 100) { nodes { number } }
+      }
+    }
+  }
+}
+";
+
+/// The search query for the pull requests merged from `from` to `to`.
+fn search_query(owner: &str, repo: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    format!(
+        "repo:{}/{} is:pr is:merged merged:{}..{}",
+        owner,
+        repo,
+        from.format(SEARCH_TIME_FORMAT),
+        to.format(SEARCH_TIME_FORMAT)
+    )
+}
+
+/// Where to split a search for the pull requests merged from `from` to `to`
+/// with `total_count` results, if the search API won't return all of them.
+fn split_search(total_count: u64, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if total_count <= SEARCH_LIMIT {
+        return None;
+    }
+
+    let seconds = (to - from).num_seconds();
+    if seconds > 0 {
+        log::debug!(
+            "{} PRs merged from {} to {}, splitting the search.",
+            total_count,
+            from,
+            to
+        );
+        Some(from + Duration::seconds(seconds / 2))
+    } else {
+        log::warn!(
+            "{} PRs were merged at {}, only the first {} are included.",
+            total_count,
+            from,
+            SEARCH_LIMIT
+        );
+        None
+    }
+}
+
+/// Changes and releases from GitHub. Merged pull requests are fetched in
+/// bulk with the GraphQL API when authenticated, as it requires a token, and
+/// with the REST API otherwise.
+pub struct GitHub {
+    octocrab: Octocrab,
+    /// The GraphQL endpoint, if authenticated.
+    graphql_url: Option<Url>,
+    http: Http,
+}
+
+impl GitHub {
+    pub fn new(base_url: Option<String>, token: Option<String>, http: Http) -> eyre::Result<Self> {
+        let mut builder = Octocrab::builder();
+        // GitHub Enterprise serves the REST API from `/api/v3` and the
+        // GraphQL API from `/api/graphql`.
+        let graphql_url = match &base_url {
+            Some(base_url) => {
+                Url::parse(&format!("{}/", base_url.trim_end_matches('/')))?.join("../graphql")?
+            }
+            None => Url::parse(GITHUB_GRAPHQL_URL)?,
+        };
+        if let Some(base_url) = base_url {
+            builder = builder.base_url(&*base_url)?;
+        }
+        let graphql_url = token.as_ref().map(|_| graphql_url);
+        if let Some(token) = token {
+            builder = builder.personal_token(token);
+        }
+
+        Ok(Self {
+            octocrab: builder.build()?,
+            graphql_url,
+            http,
+        })
+    }
+
+    /// Gets `route`, either relative to the API's base url or absolute.
+    async fn get<R: FromResponse>(
+        &self,
+        route: impl AsRef<str>,
+        query: Option<&impl Serialize>,
+    ) -> eyre::Result<R> {
+        let url = self.octocrab.absolute_url(route)?;
+        let mut request = self.octocrab.request_builder(url, Method::GET);
+        if let Some(query) = query {
+            request = request.query(query);
+        }
+
+        Ok(R::from_response(self.http.get(request, false).await?).await?)
+    }
+
+    /// Gets `route` of a merged pull request's files or commits, which don't
+    /// change, from the cache if it is there.
+    async fn get_merged<R: FromResponse>(&self, route: impl AsRef<str>) -> eyre::Result<R> {
+        let url = self.octocrab.absolute_url(route)?;
+        let request = self.octocrab.request_builder(url, Method::GET);
+
+        Ok(R::from_response(self.http.get(request, true).await?).await?)
+    }
+
+    /// Gets the items of `page` and every page after it.
+    async fn get_all<T: DeserializeOwned>(&self, page: Page<T>) -> eyre::Result<Vec<T>> {
+        let mut items = page.items;
+        let mut next = page.next;
+        while let Some(url) = next {
+            let mut page: Page<T> = self.get(url, None::<&()>).await?;
+            items.append(&mut page.items);
+            next = page.next;
+        }
+
+        Ok(items)
+    }
+
+    /// Searches for the pull requests merged from `from` to `to` with the
+    /// GraphQL API, splitting the range like `search_merged`.
+    #[async_recursion::async_recursion]
+    async fn search_merged_graphql(
+        &self,
+        graphql_url: &Url,
+        owner: &str,
+        repo: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> eyre::Result<Vec<ChangeEntry>> {
+        let query = search_query(owner, repo, from, to);
+        let mut search = self.search_graphql(graphql_url, &query, None).await?;
+
+        if let Some(middle) = split_search(search.issue_count, from, to) {
+            let mut changes = self
+                .search_merged_graphql(graphql_url, owner, repo, from, middle)
+                .await?;
+            changes.extend(
+                self.search_merged_graphql(
+                    graphql_url,
+                    owner,
+                    repo,
+                    middle + Duration::seconds(1),
+                    to,
+                )
+                .await?,
+            );
+            return Ok(changes);
+        }
+
+        let mut changes = Vec::new();
+        loop {
+            changes.extend(search.nodes.into_iter().map(ChangeEntry::from));
+            if !search.page_info.has_next_page {
+                return Ok(changes);
+            }
+            search = self
+                .search_graphql(graphql_url, &query, search.page_info.end_cursor)
+                .await?;
+        }
+    }
+
+    /// Gets the page of pull requests matching `query` after the cursor
+    /// `after` from the GraphQL API.
+    async fn search_graphql(
+        &self,
+        graphql_url: &Url,
+        query: &str,
+        after: Option<String>,
+    ) -> eyre::Result<Search> {
+        let body = serde_json::json!({
+            "query": MERGED_PULL_REQUESTS,
+            "variables": { "query": query, "after": after },
+        });
+        let request = self
+            .octocrab
+            .request_builder(graphql_url.clone(), Method::POST)
+            .json(&body);
+        let response: GraphQlResponse<SearchData> = self.http.send(request).await?.json().await?;
+
+        Ok(response.data()?.search)
+    }
+
+    /// Searches for the pull requests merged from `from` to `to`. The search
+    /// API only returns the first 1000 results, so the range is split in half
+    /// until each half has fewer results.
+    #[async_recursion::async_recursion]
+    async fn search_merged(
+        &self,
+        owner: &str,
+        repo: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> eyre::Result<Vec<SearchItem>> {
+        let query = search_query(owner, repo, from, to);
+        let page: Page<SearchItem> = self
+            .get(
+                "/search/issues",
+                Some(&[("q", &*query), ("per_page", "100")]),
+            )
+            .await?;
+
+        if let Some(middle) = split_search(page.total_count.unwrap_or_default(), from, to) {
+            let mut items = self.search_merged(owner, repo, from, middle).await?;
+            items.extend(
+                self.search_merged(owner, repo, middle + Duration::seconds(1), to)
+                    .await?,
+            );
+            return Ok(items);
+        }
+
+        self.get_all(page).await
+    }
+}
+
+/// A pull request (or issue) in the results of the search API.
+#[derive(serde::Deserialize)]
+struct SearchItem {
+    number: u64,
+    title: String,
+    html_url: String,
+    user: SearchUser,
+    labels: Vec<Name>,
+    body: Option<String>,
+    /// Only set for pull requests.
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(serde::Deserialize)]
+struct SearchUser {
+    login: String,
+    html_url: String,
+}
+
+impl From<SearchItem> for ChangeEntry {
+    fn from(item: SearchItem) -> Self {
+        Self {
+            number: item.number,
+            title: item.title,
+            html_url: item.html_url,
+            user: User {
+                login: item.user.login,
+                html_url: item.user.html_url,
+            },
+            labels: item
+                .labels
+                .into_iter()
+                .map(|label| Label { name: label.name })
+                .collect(),
+            body: item.body,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(serde::Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+impl<T> GraphQlResponse<T> {
+    fn data(self) -> eyre::Result<T> {
+        match self.data {
+            Some(data) if self.errors.is_empty() => Ok(data),
+            _ => Err(eyre::eyre!(
+                "GitHub GraphQL API error: {}",
+                self.errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SearchData {
+    search: Search,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Search {
+    issue_count: u64,
+    page_info: PageInfo,
+    nodes: Vec<PullRequest>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    #[serde(default)]
+    total_count: usize,
+    nodes: Vec<T>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequest {
+    number: u64,
+    title: String,
+    url: String,
+    body: String,
+    merged_at: Option<DateTime<Utc>>,
+    /// `None` if the author's account has been deleted.
+    author: Option<Actor>,
+    labels: Connection<Name>,
+    merge_commit: Option<Oid>,
+    base_ref_name: String,
+    head_ref_name: String,
+    milestone: Option<Title>,
+    author_association: String,
+    /// `None` if the pull request is too large to list its files.
+    files: Option<Connection<Path>>,
+    commits: Connection<PullRequestCommit>,
+    closing_issues_references: Connection<Number>,
+}
+
+#[derive(serde::Deserialize)]
+struct Actor {
+    login: String,
+    url: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Name {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Oid {
+    oid: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Title {
+    title: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Path {
+    path: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Number {
+    number: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct PullRequestCommit {
+    commit: Message,
+}
+
+#[derive(serde::Deserialize)]
+struct Message {
+    message: String,
+}
+
+impl From<PullRequest> for ChangeEntry {
+    fn from(pull: PullRequest) -> Self {
+        let mut extras = BTreeMap::new();
+        if let Some(milestone) = pull.milestone {
+            extras.insert(String::from("milestone"), milestone.title.into());
+        }
+        extras.insert(
+            String::from("author_association"),
+            pull.author_association.into(),
+        );
+
+        Self {
+            number: pull.number,
+            title: pull.title,
+            html_url: pull.url,
+            user: match pull.author {
+                Some(author) => User {
+                    login: author.login,
+                    html_url: author.url,
+                },
+                None => User {
+                    login: String::from("ghost"),
+                    html_url: String::from("https://github.com/ghost"),
+                },
+            },
+            labels: pull
+                .labels
+                .nodes
+                .into_iter()
+                .map(|label| Label { name: label.name })
+                .collect(),
+            body: Some(pull.body).filter(|body| !body.is_empty()),
+            merged_at: pull.merged_at,
+            merge_commit_sha: pull.merge_commit.map(|commit| commit.oid),
+            base_branch: Some(pull.base_ref_name),
+            head_branch: Some(pull.head_ref_name),
+            linked_issues: pull
+                .closing_issues_references
+                .nodes
+                .into_iter()
+                .map(|issue| issue.number)
+                .collect(),
+            extras,
+            // Left empty for `changed_files` to list if they don't all fit.
+            files: pull
+                .files
+                .filter(|files| files.nodes.len() >= files.total_count)
+                .map(|files| files.nodes.into_iter().map(|file| file.path).collect())
+                .unwrap_or_default(),
+            // Left empty for `commit_messages` to list if they don't all fit.
+            commit_messages: if pull.commits.nodes.len() >= pull.commits.total_count {
+                pull.commits
+                    .nodes
+                    .into_iter()
+                    .map(|commit| commit.commit.message)
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct PullRequestDetails {
+    title: String,
+    body: Option<String>,
+    merged_at: Option<DateTime<Utc>>,
+    merge_commit_sha: Option<String>,
+    base: Ref,
+    head: Ref,
+    milestone: Option<Title>,
+    author_association: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct Ref {
+    #[serde(rename = "ref")]
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct File {
+    filename: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Commit {
+    commit: CommitDetails,
+}
+
+#[derive(serde::Deserialize)]
+struct CommitDetails {
+    message: String,
+    committer: Signature,
+}
+
+#[derive(serde::Deserialize)]
+struct Signature {
+    date: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize)]
+struct Repository {
+    created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    created_at: DateTime<Utc>,
+    published_at: DateTime<Utc>,
+}
+
+impl From<GitHubRelease> for Release {
+    fn from(release: GitHubRelease) -> Self {
+        Self {
+            tag_name: release.tag_name,
+            created_at: release.created_at,
+            published_at: release.published_at,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for GitHub {
+    async fn merged_changes(
+        &self,
+        owner: &str,
+        repo: &str,
+        range: &Range,
+    ) -> eyre::Result<Vec<ChangeEntry>> {
+        if let Some(graphql_url) = &self.graphql_url {
+            let mut changes = self
+                .search_merged_graphql(graphql_url, owner, repo, range.from, range.to)
+                .await?;
+            changes.sort_by_key(|change| std::cmp::Reverse(change.merged_at));
+            return Ok(changes);
+        }
+
+        let mut numbers = HashSet::new();
+        let items = self
+            .search_merged(owner, repo, range.from, range.to)
+            .await?
+            .into_iter()
+            .filter(|item| item.pull_request.is_some() && numbers.insert(item.number));
+
+        Ok(items.map(ChangeEntry::from).collect())
+    }
+
+    async fn change_details(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: ChangeEntry,
+    ) -> eyre::Result<ChangeEntry> {
+        // The GraphQL API already had all of the details.
+        if self.graphql_url.is_some() {
+            return Ok(change);
+        }
+
+        let route = format!("/repos/{}/{}/pulls/{}", owner, repo, change.number);
+        // A merged pull request's title, body and milestone can still be
+        // edited, so unlike its files it's revalidated.
+        let pull: PullRequestDetails = self.get(route, None::<&()>).await?;
+
+        let mut extras = change.extras;
+        if let Some(milestone) = pull.milestone {
+            extras.insert(String::from("milestone"), milestone.title.into());
+        }
+        if let Some(association) = pull.author_association {
+            extras.insert(String::from("author_association"), association.into());
+        }
+
+        Ok(ChangeEntry {
+            title: pull.title,
+            body: pull.body,
+            merged_at: pull.merged_at,
+            merge_commit_sha: pull.merge_commit_sha,
+            base_branch: Some(pull.base.name),
+            head_branch: Some(pull.head.name),
+            extras,
+            ..change
+        })
+    }
+
+    async fn changed_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        if self.graphql_url.is_some() && !change.files.is_empty() {
+            return Ok(change.files.clone());
+        }
+
+        let route = format!(
+            "/repos/{}/{}/pulls/{}/files?per_page=100",
+            owner, repo, change.number
+        );
+        let page: Page<File> = self.get_merged(route).await?;
+        let files = self.get_all(page).await?;
+
+        Ok(files.into_iter().map(|file| file.filename).collect())
+    }
+
+    async fn commit_messages(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        if self.graphql_url.is_some() && !change.commit_messages.is_empty() {
+            return Ok(change.commit_messages.clone());
+        }
+
+        let route = format!(
+            "/repos/{}/{}/pulls/{}/commits?per_page=100",
+            owner, repo, change.number
+        );
+        let page: Page<Commit> = self.get_merged(route).await?;
+        let commits = self.get_all(page).await?;
+
+        Ok(commits
+            .into_iter()
+            .map(|commit| commit.commit.message)
+            .collect())
+    }
+
+    async fn latest_release(&self, owner: &str, repo: &str) -> eyre::Result<Release> {
+        let route = format!("/repos/{}/{}/releases/latest", owner, repo);
+        let release: GitHubRelease = self.get(route, None::<&()>).await.map_err(|error| {
+            NotFound::release(error, format!("`{}/{}` has no releases.", owner, repo))
+        })?;
+
+        Ok(release.into())
+    }
+
+    async fn release(&self, owner: &str, repo: &str, tag: &str) -> eyre::Result<Release> {
+        let route = format!("/repos/{}/{}/releases/tags/{}", owner, repo, tag);
+        let release: GitHubRelease = self.get(route, None::<&()>).await.map_err(|error| {
+            NotFound::release(
+                error,
+                format!("`{}/{}` has no release for `{}`.", owner, repo, tag),
+            )
+        })?;
+
+        Ok(release.into())
+    }
+
+    async fn releases(&self, owner: &str, repo: &str) -> eyre::Result<Vec<Release>> {
+        let route = format!("/repos/{}/{}/releases?per_page=100", owner, repo);
+        let page: Page<GitHubRelease> = self.get(route, None::<&()>).await?;
+        let releases = self.get_all(page).await?;
+
+        Ok(releases.into_iter().map(Release::from).collect())
+    }
+
+    async fn tags(&self, owner: &str, repo: &str) -> eyre::Result<Vec<String>> {
+        let route = format!("/repos/{}/{}/tags?per_page=100", owner, repo);
+        let page: Page<Tag> = self.get(route, None::<&()>).await?;
+        let tags = self.get_all(page).await?;
+
+        Ok(tags.into_iter().map(|tag| tag.name).collect())
+    }
+
+    async fn reference_date(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> eyre::Result<DateTime<Utc>> {
+        let route = format!("/repos/{}/{}/commits/{}", owner, repo, reference);
+        let commit: Commit = self.get(route, None::<&()>).await?;
+
+        Ok(commit.commit.committer.date)
+    }
+
+    async fn repository_start(&self, owner: &str, repo: &str) -> eyre::Result<DateTime<Utc>> {
+        let route = format!("/repos/{}/{}", owner, repo);
+        let repository: Repository = self.get(route, None::<&()>).await?;
+
+        Ok(repository.created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn pull_request(number: u64, merged_at: &str) -> serde_json::Value {
+        json!({
+            "number": number,
+            "title": format!("Pull request {}", number),
+            "url": format!("https://github.com/EmbarkStudios/relnotes/pull/{}", number),
+            "body": "Description",
+            "mergedAt": merged_at,
+            "author": { "login": "octocat", "url": "https://github.com/octocat" },
+            "labels": { "nodes": [{ "name": "bug" }] },
+            "mergeCommit": { "oid": "0123abc" },
+            "baseRefName": "main",
+            "headRefName": "feature",
+            "milestone": null,
+            "authorAssociation": "MEMBER",
+            "files": { "totalCount": 2, "nodes": [{ "path": "src/main.rs" }, { "path": "README.md" }] },
+            "commits": { "totalCount": 1, "nodes": [{ "commit": { "message": "Fix crash" } }] },
+            "closingIssuesReferences": { "nodes": [{ "number": 5 }] },
+        })
+    }
+
+    fn page(
+        issue_count: u64,
+        nodes: Vec<serde_json::Value>,
+        end_cursor: Option<&str>,
+    ) -> serde_json::Value {
+        json!({
+            "data": {
+                "search": {
+                    "issueCount": issue_count,
+                    "pageInfo": { "hasNextPage": end_cursor.is_some(), "endCursor": end_cursor },
+                    "nodes": nodes,
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn merged_changes_with_graphql() {
+        let server = MockServer::start().await;
+        for (merged, after, results) in [
+            (
+                "2021-01-01T00:00:00Z..2021-02-01T00:00:00Z",
+                None,
+                page(1500, Vec::new(), None),
+            ),
+            (
+                "2021-01-01T00:00:00Z..2021-01-16T12:00:00Z",
+                None,
+                page(
+                    2,
+                    vec![pull_request(1, "2021-01-05T00:00:00Z")],
+                    Some("next"),
+                ),
+            ),
+            (
+                "2021-01-01T00:00:00Z..2021-01-16T12:00:00Z",
+                Some("next"),
+                page(2, vec![pull_request(2, "2021-01-10T00:00:00Z")], None),
+            ),
+            (
+                "2021-01-16T12:00:01Z..2021-02-01T00:00:00Z",
+                None,
+                page(1, vec![pull_request(3, "2021-01-20T00:00:00Z")], None),
+            ),
+        ] {
+            let query = format!(
+                "repo:EmbarkStudios/relnotes is:pr is:merged merged:{}",
+                merged
+            );
+            Mock::given(method("POST"))
+                .and(path("/graphql"))
+                .and(header("Authorization", "Bearer secret"))
+                .and(body_partial_json(
+                    json!({ "variables": { "query": query, "after": after } }),
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(results))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let github = GitHub::new(
+            Some(server.uri()),
+            Some(String::from("secret")),
+            Http::default(),
+        )
+        .unwrap();
+        let range = Range {
+            from: "2021-01-01T00:00:00Z".parse().unwrap(),
+            to: "2021-02-01T00:00:00Z".parse().unwrap(),
+            from_reference: None,
+            to_reference: None,
+        };
+        let changes = github
+            .merged_changes("EmbarkStudios", "relnotes", &range)
+            .await
+            .unwrap();
+
+        // The range had too many results, so it was split in half.
+        assert_eq!(
+            changes.iter().map(|c| c.number).collect::<Vec<_>>(),
+            [3, 2, 1]
+        );
+        let change = github
+            .change_details("EmbarkStudios", "relnotes", changes[0].clone())
+            .await
+            .unwrap();
+        assert_eq!(change.merge_commit_sha.as_deref(), Some("0123abc"));
+        assert_eq!(change.base_branch.as_deref(), Some("main"));
+        assert_eq!(change.linked_issues, [5]);
+        assert_eq!(change.extras["author_association"], "MEMBER");
+        assert_eq!(
+            github
+                .changed_files("EmbarkStudios", "relnotes", &change)
+                .await
+                .unwrap(),
+            ["src/main.rs", "README.md"]
+        );
+        assert_eq!(
+            github
+                .commit_messages("EmbarkStudios", "relnotes", &change)
+                .await
+                .unwrap(),
+            ["Fix crash"]
+        );
+    }
+
+    fn search_results(total_count: u64, numbers: &[u64]) -> serde_json::Value {
+        json!({
+            "total_count": total_count,
+            "incomplete_results": false,
+            "items": numbers.iter().map(|number| json!({
+                "number": number,
+                "title": format!("Pull request {}", number),
+                "html_url": format!("https://github.com/EmbarkStudios/relnotes/pull/{}", number),
+                "user": { "login": "octocat", "html_url": "https://github.com/octocat" },
+                "labels": [],
+                "body": null,
+                "pull_request": {},
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    #[tokio::test]
+    async fn splits_large_searches() {
+        let server = MockServer::start().await;
+        for (query, results) in [
+            (
+                "merged:2021-01-01T00:00:00Z..2021-01-03T00:00:00Z",
+                search_results(1500, &[]),
+            ),
+            (
+                "merged:2021-01-01T00:00:00Z..2021-01-02T00:00:00Z",
+                search_results(2, &[2, 1]),
+            ),
+            (
+                "merged:2021-01-02T00:00:01Z..2021-01-03T00:00:00Z",
+                search_results(2, &[3, 2]),
+            ),
+        ] {
+            Mock::given(method("GET"))
+                .and(path("/search/issues"))
+                .and(query_param(
+                    "q",
+                    format!("repo:EmbarkStudios/relnotes is:pr is:merged {}", query),
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(results))
+                .mount(&server)
+                .await;
+        }
+
+        let github = GitHub::new(Some(server.uri()), None, Http::default()).unwrap();
+        let range = Range {
+            from: "2021-01-01T00:00:00Z".parse().unwrap(),
+            to: "2021-01-03T00:00:00Z".parse().unwrap(),
+            from_reference: None,
+            to_reference: None,
+        };
+        let changes = github
+            .merged_changes("EmbarkStudios", "relnotes", &range)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            changes.iter().map(|c| c.number).collect::<Vec<_>>(),
+            [2, 1, 3]
+        );
+    }
+}
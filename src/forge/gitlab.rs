@@ -0,0 +1,551 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use super::{
+    http::{Http, NotFound},
+    ChangeEntry, Forge, Label, Range, Release, User,
+};
+use crate::error::Error;
+
+const GITLAB_BASE_URL: &str = "https://gitlab.com";
+
+/// Changes and releases from a GitLab instance, using the REST API.
+pub struct GitLab {
+    client: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+    http: Http,
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    iid: u64,
+    title: String,
+    description: Option<String>,
+    web_url: String,
+    author: Author,
+    #[serde(default)]
+    labels: Vec<String>,
+    merged_at: Option<DateTime<Utc>>,
+    merge_commit_sha: Option<String>,
+    squash_commit_sha: Option<String>,
+    source_branch: String,
+    target_branch: String,
+    milestone: Option<Milestone>,
+}
+
+#[derive(Deserialize)]
+struct Milestone {
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct Diff {
+    new_path: String,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    username: String,
+    web_url: String,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+    message: String,
+    committed_date: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    created_at: DateTime<Utc>,
+    released_at: DateTime<Utc>,
+}
+
+impl From<MergeRequest> for ChangeEntry {
+    fn from(merge_request: MergeRequest) -> Self {
+        let mut extras = BTreeMap::new();
+        if let Some(milestone) = merge_request.milestone {
+            extras.insert(String::from("milestone"), milestone.title.into());
+        }
+
+        Self {
+            number: merge_request.iid,
+            title: merge_request.title,
+            html_url: merge_request.web_url,
+            user: merge_request.author.into(),
+            labels: merge_request
+                .labels
+                .into_iter()
+                .map(|name| Label { name })
+                .collect(),
+            body: merge_request.description,
+            merged_at: merge_request.merged_at,
+            merge_commit_sha: merge_request
+                .merge_commit_sha
+                .or(merge_request.squash_commit_sha),
+            base_branch: Some(merge_request.target_branch),
+            head_branch: Some(merge_request.source_branch),
+            extras,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<Author> for User {
+    fn from(author: Author) -> Self {
+        Self {
+            login: author.username,
+            html_url: author.web_url,
+        }
+    }
+}
+
+impl From<GitLabRelease> for Release {
+    fn from(release: GitLabRelease) -> Self {
+        Self {
+            tag_name: release.tag_name,
+            created_at: release.created_at,
+            published_at: release.released_at,
+        }
+    }
+}
+
+impl GitLab {
+    pub fn new(base_url: Option<String>, token: Option<String>, http: Http) -> eyre::Result<Self> {
+        let base_url = Url::parse(base_url.as_deref().unwrap_or(GITLAB_BASE_URL))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base_url,
+            token,
+            http,
+        })
+    }
+
+    /// Returns the url for `segments` of the project `owner/repo`.
+    fn project_url(&self, owner: &str, repo: &str, segments: &[&str]) -> eyre::Result<Url> {
+        let project = format!("{}/{}", owner, repo);
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|()| eyre::eyre!("`{}` is not a valid base url.", self.base_url))?
+            .pop_if_empty()
+            .extend(&["api", "v4", "projects", &project])
+            .extend(segments);
+
+        Ok(url)
+    }
+
+    /// Gets `url`, from the cache if it is `immutable`, such as the diffs and
+    /// commits of a merged merge request.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        query: &[(&str, String)],
+        immutable: bool,
+    ) -> eyre::Result<(T, Option<String>)> {
+        let mut request = self.client.get(url).query(query);
+        if let Some(token) = &self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+
+        let response = self.http.get(request, immutable).await?;
+        let next_page = response
+            .headers()
+            .get("x-next-page")
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(String::from);
+
+        Ok((response.json().await?, next_page))
+    }
+
+    /// Gets every page of results from `url`.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        query: &[(&str, String)],
+        immutable: bool,
+    ) -> eyre::Result<Vec<T>> {
+        let mut query = query.to_vec();
+        query.push(("per_page", String::from("100")));
+
+        let (mut items, mut next_page) = self.get::<Vec<T>>(url.clone(), &query, immutable).await?;
+        while let Some(page) = next_page {
+            let mut page_query = query.clone();
+            page_query.push(("page", page));
+            let (mut page_items, page) = self
+                .get::<Vec<T>>(url.clone(), &page_query, immutable)
+                .await?;
+            items.append(&mut page_items);
+            next_page = page;
+        }
+
+        Ok(items)
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for GitLab {
+    async fn merged_changes(
+        &self,
+        owner: &str,
+        repo: &str,
+        range: &Range,
+    ) -> eyre::Result<Vec<ChangeEntry>> {
+        let url = self.project_url(owner, repo, &["merge_requests"])?;
+        let merge_requests = self
+            .get_all::<MergeRequest>(
+                url,
+                &[
+                    ("state", String::from("merged")),
+                    ("merged_after", range.from.to_rfc3339()),
+                    ("merged_before", range.to.to_rfc3339()),
+                ],
+                false,
+            )
+            .await?;
+
+        Ok(merge_requests.into_iter().map(ChangeEntry::from).collect())
+    }
+
+    async fn change_details(
+        &self,
+        _owner: &str,
+        _repo: &str,
+        change: ChangeEntry,
+    ) -> eyre::Result<ChangeEntry> {
+        // Merge request listings already contain every detail.
+        Ok(change)
+    }
+
+    async fn changed_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        let iid = change.number.to_string();
+        let url = self.project_url(owner, repo, &["merge_requests", &iid, "diffs"])?;
+        let diffs = self.get_all::<Diff>(url, &[], true).await?;
+
+        Ok(diffs.into_iter().map(|diff| diff.new_path).collect())
+    }
+
+    async fn commit_messages(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        let iid = change.number.to_string();
+        let url = self.project_url(owner, repo, &["merge_requests", &iid, "commits"])?;
+        let commits = self.get_all::<Commit>(url, &[], true).await?;
+
+        Ok(commits.into_iter().map(|commit| commit.message).collect())
+    }
+
+    async fn latest_release(&self, owner: &str, repo: &str) -> eyre::Result<Release> {
+        let url = self.project_url(owner, repo, &["releases"])?;
+        let (releases, _) = self
+            .get::<Vec<GitLabRelease>>(
+                url,
+                &[
+                    ("order_by", String::from("released_at")),
+                    ("sort", String::from("desc")),
+                    ("per_page", String::from("1")),
+                ],
+                false,
+            )
+            .await?;
+
+        releases
+            .into_iter()
+            .next()
+            .map(Release::from)
+            .ok_or_else(|| {
+                Error::ReleaseNotFound(format!("`{}/{}` has no releases.", owner, repo)).into()
+            })
+    }
+
+    async fn release(&self, owner: &str, repo: &str, tag: &str) -> eyre::Result<Release> {
+        let url = self.project_url(owner, repo, &["releases", tag])?;
+        let (release, _) = self
+            .get::<GitLabRelease>(url, &[], false)
+            .await
+            .map_err(|error| {
+                NotFound::release(
+                    error,
+                    format!("`{}/{}` has no release for `{}`.", owner, repo, tag),
+                )
+            })?;
+
+        Ok(release.into())
+    }
+
+    async fn releases(&self, owner: &str, repo: &str) -> eyre::Result<Vec<Release>> {
+        let url = self.project_url(owner, repo, &["releases"])?;
+        let releases = self.get_all::<GitLabRelease>(url, &[], false).await?;
+
+        Ok(releases.into_iter().map(Release::from).collect())
+    }
+
+    async fn tags(&self, owner: &str, repo: &str) -> eyre::Result<Vec<String>> {
+        let url = self.project_url(owner, repo, &["repository", "tags"])?;
+        let tags = self.get_all::<Tag>(url, &[], false).await?;
+
+        Ok(tags.into_iter().map(|tag| tag.name).collect())
+    }
+
+    async fn reference_date(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> eyre::Result<DateTime<Utc>> {
+        let url = self.project_url(owner, repo, &["repository", "commits", reference])?;
+        let (commit, _) = self.get::<Commit>(url, &[], false).await?;
+
+        Ok(commit.committed_date)
+    }
+
+    async fn repository_start(&self, owner: &str, repo: &str) -> eyre::Result<DateTime<Utc>> {
+        let url = self.project_url(owner, repo, &[])?;
+        let (project, _) = self.get::<Project>(url, &[], false).await?;
+
+        Ok(project.created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn merge_request(iid: u64, labels: &[&str]) -> serde_json::Value {
+        json!({
+            "iid": iid,
+            "title": format!("Merge request {}", iid),
+            "description": "Description",
+            "web_url": format!("https://gitlab.example.com/group/project/-/merge_requests/{}", iid),
+            "author": {
+                "username": "erin",
+                "web_url": "https://gitlab.example.com/erin",
+            },
+            "labels": labels,
+            "merged_at": "2021-01-10T12:00:00Z",
+            "merge_commit_sha": null,
+            "squash_commit_sha": "0123abc",
+            "source_branch": "feature",
+            "target_branch": "main",
+            "milestone": { "title": "1.0" },
+        })
+    }
+
+    fn release(tag: &str, released_at: &str) -> serde_json::Value {
+        json!({
+            "tag_name": tag,
+            "created_at": released_at,
+            "released_at": released_at,
+        })
+    }
+
+    fn gitlab(server: &MockServer) -> GitLab {
+        GitLab::new(
+            Some(server.uri()),
+            Some(String::from("secret")),
+            Http::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merged_changes_follows_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/group%2Fproject/merge_requests"))
+            .and(query_param("state", "merged"))
+            .and(query_param("merged_after", "2021-01-01T00:00:00+00:00"))
+            .and(query_param("merged_before", "2021-02-01T00:00:00+00:00"))
+            .and(query_param("page", "2"))
+            .and(header("PRIVATE-TOKEN", "secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([merge_request(2, &[])])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/group%2Fproject/merge_requests"))
+            .and(query_param("state", "merged"))
+            .and(header("PRIVATE-TOKEN", "secret"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-next-page", "2")
+                    .set_body_json(json!([merge_request(1, &["bug", "dependencies"])])),
+            )
+            .mount(&server)
+            .await;
+
+        let range = Range {
+            from: "2021-01-01T00:00:00Z".parse().unwrap(),
+            to: "2021-02-01T00:00:00Z".parse().unwrap(),
+            from_reference: None,
+            to_reference: None,
+        };
+        let changes = gitlab(&server)
+            .merged_changes("group", "project", &range)
+            .await
+            .unwrap();
+
+        assert_eq!(changes.iter().map(|c| c.number).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(
+            changes[0]
+                .labels
+                .iter()
+                .map(|l| &*l.name)
+                .collect::<Vec<_>>(),
+            ["bug", "dependencies"]
+        );
+        assert_eq!(changes[0].user.login, "erin");
+        assert_eq!(changes[0].body.as_deref(), Some("Description"));
+        assert_eq!(changes[0].base_branch.as_deref(), Some("main"));
+        assert_eq!(changes[0].head_branch.as_deref(), Some("feature"));
+        assert_eq!(changes[0].extras["milestone"], "1.0");
+        assert_eq!(changes[0].merge_commit_sha.as_deref(), Some("0123abc"));
+    }
+
+    #[tokio::test]
+    async fn changed_files_lists_new_paths() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/api/v4/projects/group%2Fproject/merge_requests/1/diffs",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "old_path": "src/old.rs", "new_path": "src/new.rs" },
+                { "old_path": "README.md", "new_path": "README.md" },
+            ])))
+            .mount(&server)
+            .await;
+
+        let change = ChangeEntry::from(
+            serde_json::from_value::<MergeRequest>(merge_request(1, &[])).unwrap(),
+        );
+        let files = gitlab(&server)
+            .changed_files("group", "project", &change)
+            .await
+            .unwrap();
+
+        assert_eq!(files, ["src/new.rs", "README.md"]);
+    }
+
+    #[tokio::test]
+    async fn commit_messages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/api/v4/projects/group%2Fproject/merge_requests/1/commits",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {
+                    "message": "Rename `run`\n\nBREAKING CHANGE: `run` is now `execute`.",
+                    "committed_date": "2021-01-10T12:00:00Z",
+                },
+            ])))
+            .mount(&server)
+            .await;
+
+        let change = ChangeEntry::from(
+            serde_json::from_value::<MergeRequest>(merge_request(1, &[])).unwrap(),
+        );
+        let messages = gitlab(&server)
+            .commit_messages("group", "project", &change)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            messages,
+            ["Rename `run`\n\nBREAKING CHANGE: `run` is now `execute`."]
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_release_uses_most_recent_release() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/group%2Fproject/releases"))
+            .and(query_param("order_by", "released_at"))
+            .and(query_param("sort", "desc"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([release("v0.2.0", "2021-01-05T00:00:00Z")])),
+            )
+            .mount(&server)
+            .await;
+
+        let release = gitlab(&server)
+            .latest_release("group", "project")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            release.published_at,
+            "2021-01-05T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_release_errors_without_releases() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/group%2Fproject/releases"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&server)
+            .await;
+
+        let error = gitlab(&server)
+            .latest_release("group", "project")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::exit_code(&error), 4);
+    }
+
+    #[tokio::test]
+    async fn release_by_tag() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects/group%2Fproject/releases/v0.1.0"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(release("v0.1.0", "2020-12-01T00:00:00Z")),
+            )
+            .mount(&server)
+            .await;
+
+        let release = gitlab(&server)
+            .release("group", "project", "v0.1.0")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            release.published_at,
+            "2020-12-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,509 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{
+    header::{self, HeaderMap},
+    RequestBuilder, Response, StatusCode,
+};
+use sha1::{Digest, Sha1};
+
+use super::fixtures::Fixtures;
+use crate::error::Error;
+
+/// How often a request is retried before its error is returned.
+const MAX_RETRIES: u32 = 5;
+/// How long to wait before the first retry, doubling with every retry after.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Sends requests to forges, waiting out rate limits and retrying transient
+/// failures with exponential backoff.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_retries: MAX_RETRIES,
+            initial_backoff: INITIAL_BACKOFF,
+        }
+    }
+}
+
+impl Retry {
+    /// Sends `request`, returning its response once it succeeds, or once it
+    /// fails with an error that isn't transient or it has been retried
+    /// `max_retries` times. If the response says the rate limit is used up,
+    /// this waits for it to reset before returning so the next request
+    /// doesn't fail.
+    pub async fn send(&self, request: RequestBuilder) -> eyre::Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let retry = request
+                .try_clone()
+                .ok_or_else(|| eyre::eyre!("Can't retry a request with a streaming body."))?;
+            let wait = match retry.send().await {
+                Ok(response)
+                    if response.status().is_success()
+                        || response.status() == StatusCode::NOT_MODIFIED =>
+                {
+                    if let Some(wait) = rate_limit_reset(response.headers()) {
+                        log::warn!(
+                            "Rate limit reached, waiting {}s for it to reset.",
+                            wait.as_secs()
+                        );
+                        tokio::time::sleep(wait).await;
+                    }
+                    return Ok(response);
+                }
+                Ok(response) if attempt < self.max_retries && is_transient(&response) => {
+                    log::warn!(
+                        "`{}` responded with {}, retrying.",
+                        response.url(),
+                        response.status()
+                    );
+                    retry_after(response.headers())
+                        .or_else(|| rate_limit_reset(response.headers()))
+                        .unwrap_or_else(|| self.backoff(attempt))
+                }
+                Ok(response) => return Ok(response),
+                Err(error)
+                    if attempt < self.max_retries && (error.is_timeout() || error.is_connect()) =>
+                {
+                    log::warn!("{}, retrying.", error);
+                    self.backoff(attempt)
+                }
+                Err(error) => return Err(error.into()),
+            };
+
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff * 2u32.saturating_pow(attempt)
+    }
+}
+
+/// Sends requests with `Retry`, and caches the responses to `GET` requests
+/// in `cache` if there is one. With `fixtures` the requests are recorded to
+/// or replayed from them instead.
+#[derive(Clone, Debug, Default)]
+pub struct Http {
+    retry: Retry,
+    cache: Option<Cache>,
+    fixtures: Option<Arc<Fixtures>>,
+}
+
+impl Http {
+    pub fn new(cache: Option<Cache>) -> Self {
+        Self {
+            retry: Retry::default(),
+            cache,
+            fixtures: None,
+        }
+    }
+
+    /// Records requests and their responses to `fixtures`, bypassing the
+    /// cache so every request is recorded.
+    pub fn with_fixtures(fixtures: Fixtures) -> Self {
+        Self {
+            retry: Retry::default(),
+            cache: None,
+            fixtures: Some(Arc::new(fixtures)),
+        }
+    }
+
+    /// Sends `request` without caching its response, returning an error if
+    /// it fails.
+    pub async fn send(&self, request: RequestBuilder) -> eyre::Result<Response> {
+        let response = match &self.fixtures {
+            Some(fixtures) => fixtures.send(&self.retry, request).await?,
+            None => self.retry.send(request).await?,
+        };
+
+        if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+            Ok(response)
+        } else {
+            Err(status_error(response).await)
+        }
+    }
+
+    /// Sends the `GET` `request`. A cached response is revalidated with its
+    /// `ETag` or `Last-Modified` header, or returned as is if the resource is
+    /// `immutable`, such as the files of a merged change.
+    pub async fn get(&self, request: RequestBuilder, immutable: bool) -> eyre::Result<Response> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.send(request).await,
+        };
+        let url = request
+            .try_clone()
+            .ok_or_else(|| eyre::eyre!("Can't cache a request with a streaming body."))?
+            .build()?
+            .url()
+            .to_string();
+
+        let cached = cache.read(&url).await;
+        let mut request = request;
+        if let Some(entry) = &cached {
+            if immutable {
+                log::debug!("Using the cached response for `{}`.", url);
+                return entry.response();
+            }
+            if let Some(etag) = entry.headers.get("etag") {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = entry.headers.get("last-modified") {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            log::debug!("`{}` hasn't changed since it was cached.", url);
+            return cached
+                .ok_or_else(|| eyre::eyre!("`{}` responded with 304 without a request.", url))?
+                .response();
+        }
+        let headers = response.headers();
+        if !immutable
+            && !headers.contains_key(header::ETAG)
+            && !headers.contains_key(header::LAST_MODIFIED)
+        {
+            return Ok(response);
+        }
+
+        let entry = Entry::from_response(url, response).await?;
+        if let Err(error) = cache.write(&entry).await {
+            log::warn!("Couldn't cache `{}`: {}", entry.url, error);
+        }
+        entry.response()
+    }
+}
+
+/// Responses stored on disk, one JSON file per URL.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    directory: PathBuf,
+}
+
+impl Cache {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// `relnotes` in the user's cache directory: `$XDG_CACHE_HOME`,
+    /// `~/.cache`, or `%LOCALAPPDATA%` on Windows.
+    pub fn default_directory() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .or_else(|| var("LOCALAPPDATA").map(PathBuf::from))
+            .map(|directory| directory.join("relnotes"))
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let hash = Sha1::digest(url.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        self.directory.join(format!("{}.json", hash))
+    }
+
+    /// The cached response for `url`. Unreadable entries count as missing,
+    /// and are replaced by the next response.
+    async fn read(&self, url: &str) -> Option<Entry> {
+        let contents = tokio::fs::read(self.path(url)).await.ok()?;
+
+        serde_json::from_slice::<Entry>(&contents)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    async fn write(&self, entry: &Entry) -> eyre::Result<()> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(self.path(&entry.url), serde_json::to_vec(entry)?).await?;
+
+        Ok(())
+    }
+}
+
+/// A successful response in the cache.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Entry {
+    url: String,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+impl Entry {
+    async fn from_response(url: String, response: Response) -> eyre::Result<Self> {
+        let headers = response
+            .headers()
+            .iter()
+            // The body is stored decoded and whole.
+            .filter(|(name, _)| {
+                ![
+                    header::CONTENT_ENCODING,
+                    header::CONTENT_LENGTH,
+                    header::TRANSFER_ENCODING,
+                ]
+                .contains(name)
+            })
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+
+        Ok(Self {
+            url,
+            headers,
+            body: response.text().await?,
+        })
+    }
+
+    fn response(&self) -> eyre::Result<Response> {
+        let mut response = http::Response::builder();
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+
+        Ok(response.body(self.body.clone())?.into())
+    }
+}
+
+/// Whether the request failed because of a server error or a (secondary)
+/// rate limit, and might succeed if it is sent again.
+fn is_transient(response: &Response) -> bool {
+    let status = response.status();
+    let headers = response.headers();
+
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && (headers.contains_key("retry-after") || rate_limit_reset(headers).is_some()))
+}
+
+/// The time to wait from the `Retry-After` header, in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// The time until the rate limit resets, if there are no requests left.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name)?.to_str().ok()?.parse::<u64>().ok();
+
+    if header("x-ratelimit-remaining")? > 0 {
+        return None;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // Wait a second longer, in case our clock is behind the forge's.
+    Some(Duration::from_secs(
+        header("x-ratelimit-reset")?.saturating_sub(now) + 1,
+    ))
+}
+
+/// A request for something the forge doesn't have, e.g. a release that
+/// doesn't exist.
+#[derive(Debug)]
+pub struct NotFound(String);
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+impl NotFound {
+    /// Turns `error` into `Error::ReleaseNotFound` with `message`, if the
+    /// forge didn't find what the request was for.
+    pub fn release(error: eyre::Report, message: String) -> eyre::Report {
+        if error.is::<Self>() {
+            Error::ReleaseNotFound(message).into()
+        } else {
+            error
+        }
+    }
+}
+
+/// An error for the failed `response`, with the message from its body.
+async fn status_error(response: Response) -> eyre::Report {
+    #[derive(serde::Deserialize)]
+    struct Message {
+        message: String,
+    }
+
+    let status = response.status();
+    let rate_limited = status == StatusCode::TOO_MANY_REQUESTS || is_transient(&response);
+    let url = response.url().clone();
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Message>(&body)
+        .map(|body| body.message)
+        .unwrap_or(body);
+    let message = format!("`{}` responded with {}: {}", url, status, message.trim());
+
+    match status {
+        StatusCode::UNAUTHORIZED => Error::Authentication(message).into(),
+        StatusCode::NOT_FOUND => NotFound(message).into(),
+        _ if rate_limited && !status.is_server_error() => Error::RateLimited(message).into(),
+        _ => eyre::eyre!(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    const RETRY: Retry = Retry {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(1),
+    };
+
+    async fn get(server: &MockServer) -> eyre::Result<Response> {
+        let http = Http {
+            retry: RETRY,
+            ..Http::default()
+        };
+
+        http.send(reqwest::Client::new().get(&format!("{}/resource", server.uri())))
+            .await
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("retry-after", "0")
+                    .set_body_json(serde_json::json!({
+                        "message": "You have exceeded a secondary rate limit."
+                    })),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&server)
+            .await;
+
+        let response = get(&server).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_persistent_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(ResponseTemplate::new(502))
+            .mount(&server)
+            .await;
+
+        assert!(get(&server).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({
+                "message": "Resource not accessible by integration"
+            })))
+            .mount(&server)
+            .await;
+
+        let error = get(&server).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("403 Forbidden: Resource not accessible by integration"));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "message": "Bad credentials"
+            })))
+            .mount(&server)
+            .await;
+
+        assert_eq!(crate::error::exit_code(&get(&server).await.unwrap_err()), 5);
+    }
+
+    #[tokio::test]
+    async fn caches_responses() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/resource"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .insert_header("link", "<https://example.com/2>; rel=\"next\"")
+                    .set_body_string("cached"),
+            )
+            .mount(&server)
+            .await;
+
+        let directory = std::env::temp_dir().join(format!("relnotes-cache-{}", std::process::id()));
+        let http = Http {
+            retry: RETRY,
+            cache: Some(Cache::new(directory.clone())),
+            fixtures: None,
+        };
+        let url = format!("{}/resource", server.uri());
+        let get = |immutable| http.get(reqwest::Client::new().get(&url), immutable);
+
+        for immutable in [false, false, true] {
+            let response = get(immutable).await.unwrap();
+            assert!(response.headers().contains_key("link"));
+            assert_eq!(response.text().await.unwrap(), "cached");
+        }
+        // The immutable response didn't need a request.
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
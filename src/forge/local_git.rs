@@ -0,0 +1,535 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::process::Command;
+
+use super::{ChangeEntry, Forge, Range, Release, User};
+use crate::error::Error;
+
+const GITHUB_BASE_URL: &str = "https://github.com";
+/// Separates the fields of a single entry in git's output.
+const FIELD_SEPARATOR: char = '\x1f';
+/// Separates entries in git's output.
+const RECORD_SEPARATOR: char = '\x1e';
+
+/// Changes and releases from a local git repository, without any network
+/// access. Changes are read from the merge and squash merge commits in the
+/// first parent history, and releases are the repository's tags.
+pub struct LocalGit {
+    path: PathBuf,
+    base_url: String,
+}
+
+/// A change parsed from a merge or squash merge commit message.
+#[derive(Debug, PartialEq)]
+struct MergeMessage {
+    number: u64,
+    title: String,
+    body: Option<String>,
+    /// The author's login, if the message contains it.
+    login: Option<String>,
+    /// The branch that was merged, if the message contains it.
+    branch: Option<String>,
+}
+
+impl MergeMessage {
+    /// Parses either a merge commit (`Merge pull request #123 from
+    /// owner/branch`) or a squash merge commit (`Title (#123)`).
+    fn parse(message: &str) -> Option<Self> {
+        static MERGE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^Merge pull request #(?P<number>\d+) from (?P<login>[^/\s]+)/(?P<branch>\S+)$",
+            )
+            .unwrap()
+        });
+        static SQUASH: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"^(?P<title>.+) \(#(?P<number>\d+)\)$").unwrap());
+
+        let message = message.trim();
+        let (subject, rest) = match message.find('\n') {
+            Some(index) => (&message[..index], message[index..].trim()),
+            None => (message, ""),
+        };
+
+        if let Some(captures) = MERGE.captures(subject) {
+            // GitHub puts the pull request's title in the first line of the
+            // body, and the pull request's description is not included.
+            let mut lines = rest.splitn(2, '\n');
+            let title = lines.next().filter(|title| !title.is_empty());
+            let body = lines
+                .next()
+                .map(str::trim)
+                .filter(|body| !body.is_empty())
+                .map(String::from);
+
+            Some(Self {
+                number: captures["number"].parse().ok()?,
+                title: title.unwrap_or(subject).to_owned(),
+                body,
+                login: Some(captures["login"].to_owned()),
+                branch: Some(captures["branch"].to_owned()),
+            })
+        } else if let Some(captures) = SQUASH.captures(subject) {
+            Some(Self {
+                number: captures["number"].parse().ok()?,
+                title: captures["title"].to_owned(),
+                body: Some(rest.to_owned()).filter(|body| !body.is_empty()),
+                login: None,
+                branch: None,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl LocalGit {
+    pub fn new(path: PathBuf, base_url: Option<String>) -> Self {
+        Self {
+            path,
+            base_url: base_url
+                .unwrap_or_else(|| String::from(GITHUB_BASE_URL))
+                .trim_end_matches('/')
+                .to_owned(),
+        }
+    }
+
+    async fn git(&self, args: &[&str]) -> eyre::Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(args)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(eyre::eyre!(
+                "`git {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Lists the tags matching `pattern`, newest first.
+    async fn tags_matching(&self, pattern: &str) -> eyre::Result<Vec<Release>> {
+        let output = self
+            .git(&[
+                "for-each-ref",
+                "--sort=-creatordate",
+                "--format=%(refname:short)%1f%(creatordate:iso-strict)",
+                pattern,
+            ])
+            .await?;
+
+        output
+            .lines()
+            .filter_map(|line| line.split_once(FIELD_SEPARATOR))
+            .map(|(tag, date)| {
+                let date = DateTime::parse_from_rfc3339(date)?.with_timezone(&Utc);
+                Ok(Release {
+                    tag_name: tag.to_owned(),
+                    created_at: date,
+                    published_at: date,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the author of a commit, using the login in GitHub's
+    /// `noreply` addresses when possible.
+    fn author(&self, name: &str, email: &str) -> User {
+        static NOREPLY: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^(?:\d+\+)?(?P<login>[^@]+)@users\.noreply\.github\.com$").unwrap()
+        });
+
+        if let Some(captures) = NOREPLY.captures(email) {
+            self.user(&captures["login"])
+        } else {
+            User {
+                login: name.to_owned(),
+                html_url: format!("mailto:{}", email),
+            }
+        }
+    }
+
+    fn user(&self, login: &str) -> User {
+        User {
+            login: login.to_owned(),
+            html_url: format!("{}/{}", self.base_url, login),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for LocalGit {
+    async fn merged_changes(
+        &self,
+        owner: &str,
+        repo: &str,
+        range: &Range,
+    ) -> eyre::Result<Vec<ChangeEntry>> {
+        let to = range.to_reference.as_deref().unwrap_or("HEAD");
+        let revisions = match &range.from_reference {
+            Some(from) => format!("{}..{}", from, to),
+            None => to.to_owned(),
+        };
+        // `--since` is inclusive, which would include the last commit of the
+        // previous release.
+        let since = format!(
+            "--since={}",
+            (range.from + Duration::seconds(1)).to_rfc3339()
+        );
+        let until = format!("--until={}", range.to.to_rfc3339());
+
+        let mut args = vec![
+            "log",
+            "--first-parent",
+            "--format=%H%x1f%cI%x1f%an%x1f%ae%x1f%B%x1e",
+            &revisions,
+        ];
+        if range.from_reference.is_none() {
+            args.push(&since);
+        }
+        if range.to_reference.is_none() {
+            args.push(&until);
+        }
+
+        let output = self.git(&args).await?;
+        let mut changes = Vec::new();
+        for record in output.split(RECORD_SEPARATOR) {
+            let fields = record
+                .trim_start()
+                .splitn(5, FIELD_SEPARATOR)
+                .collect::<Vec<_>>();
+            let (sha, date, name, email, message) = match fields[..] {
+                [sha, date, name, email, message] => (sha, date, name, email, message),
+                _ => continue,
+            };
+
+            let merge = match MergeMessage::parse(message) {
+                Some(merge) => merge,
+                None => continue,
+            };
+
+            changes.push(ChangeEntry {
+                number: merge.number,
+                title: merge.title,
+                html_url: format!("{}/{}/{}/pull/{}", self.base_url, owner, repo, merge.number),
+                user: match merge.login {
+                    Some(login) => self.user(&login),
+                    None => self.author(name, email),
+                },
+                body: merge.body,
+                merged_at: Some(DateTime::parse_from_rfc3339(date)?.with_timezone(&Utc)),
+                merge_commit_sha: Some(sha.to_owned()),
+                head_branch: merge.branch,
+                ..ChangeEntry::default()
+            });
+        }
+
+        Ok(changes)
+    }
+
+    async fn change_details(
+        &self,
+        _owner: &str,
+        _repo: &str,
+        change: ChangeEntry,
+    ) -> eyre::Result<ChangeEntry> {
+        // Commit messages are all there is to know about a change.
+        Ok(change)
+    }
+
+    async fn changed_files(
+        &self,
+        _owner: &str,
+        _repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        let sha = change
+            .merge_commit_sha
+            .as_deref()
+            .ok_or_else(|| eyre::eyre!("#{} has no merge commit.", change.number))?;
+        // Merge commits are compared against the branch they were merged into.
+        let output = self
+            .git(&[
+                "show",
+                "--format=",
+                "--name-only",
+                "-m",
+                "--first-parent",
+                sha,
+            ])
+            .await?;
+
+        Ok(output
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    async fn commit_messages(
+        &self,
+        _owner: &str,
+        _repo: &str,
+        change: &ChangeEntry,
+    ) -> eyre::Result<Vec<String>> {
+        let sha = change
+            .merge_commit_sha
+            .as_deref()
+            .ok_or_else(|| eyre::eyre!("#{} has no merge commit.", change.number))?;
+        // The commits merged by a merge commit, or the squash merged commit
+        // itself.
+        let parents = self.git(&["rev-list", "--parents", "-n", "1", sha]).await?;
+        let revisions = if parents.split_whitespace().count() > 2 {
+            format!("{}^1..{}^2", sha, sha)
+        } else {
+            format!("{}^!", sha)
+        };
+        let output = self.git(&["log", "--format=%B%x1e", &revisions]).await?;
+
+        Ok(output
+            .split(RECORD_SEPARATOR)
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    async fn latest_release(&self, _owner: &str, _repo: &str) -> eyre::Result<Release> {
+        self.tags_matching("refs/tags")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Error::ReleaseNotFound(format!("`{}` has no tags.", self.path.display())).into()
+            })
+    }
+
+    async fn release(&self, _owner: &str, _repo: &str, tag: &str) -> eyre::Result<Release> {
+        self.tags_matching(&format!("refs/tags/{}", tag))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::ReleaseNotFound(format!("Tag `{}` not found.", tag)).into())
+    }
+
+    async fn releases(&self, _owner: &str, _repo: &str) -> eyre::Result<Vec<Release>> {
+        self.tags_matching("refs/tags").await
+    }
+
+    async fn tags(&self, _owner: &str, _repo: &str) -> eyre::Result<Vec<String>> {
+        Ok(self
+            .tags_matching("refs/tags")
+            .await?
+            .into_iter()
+            .map(|tag| tag.tag_name)
+            .collect())
+    }
+
+    async fn reference_date(
+        &self,
+        _owner: &str,
+        _repo: &str,
+        reference: &str,
+    ) -> eyre::Result<DateTime<Utc>> {
+        let output = self.git(&["log", "-1", "--format=%cI", reference]).await?;
+
+        Ok(DateTime::parse_from_rfc3339(output.trim())?.with_timezone(&Utc))
+    }
+
+    async fn repository_start(&self, _owner: &str, _repo: &str) -> eyre::Result<DateTime<Utc>> {
+        // Repositories can have more than one root commit.
+        let output = self
+            .git(&["log", "--max-parents=0", "--format=%cI", "HEAD"])
+            .await?;
+
+        let dates = output
+            .lines()
+            .map(|date| Ok(DateTime::parse_from_rfc3339(date)?.with_timezone(&Utc)))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        dates
+            .into_iter()
+            .min()
+            .ok_or_else(|| eyre::eyre!("`{}` has no commits.", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `git` in `root`, committing at `date`.
+    fn git(root: &std::path::Path, date: &str, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Octocat")
+            .env("GIT_AUTHOR_EMAIL", "octocat@example.com")
+            .env("GIT_COMMITTER_NAME", "Octocat")
+            .env("GIT_COMMITTER_EMAIL", "octocat@example.com")
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .status()
+            .unwrap();
+        assert!(status.success(), "`git {}` failed", args.join(" "));
+    }
+
+    /// Creates a repository in a temporary directory with a squash merge
+    /// tagged `v0.1.0`, followed by a merge, a squash merge, and a commit
+    /// that isn't a change.
+    fn repository() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("relnotes-local-git-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let commit = |date: &str, path: &str, message: &str| {
+            std::fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            std::fs::write(root.join(path), message).unwrap();
+            git(&root, date, &["add", path]);
+            git(&root, date, &["commit", "-q", "-m", message]);
+        };
+
+        git(&root, "2021-01-01T00:00:00Z", &["init", "-q"]);
+        commit("2021-01-01T00:00:00Z", "README.md", "Initial commit");
+        commit("2021-01-02T00:00:00Z", "LICENSE", "Add license (#1)");
+        git(&root, "2021-01-02T00:00:00Z", &["tag", "v0.1.0"]);
+        git(
+            &root,
+            "2021-01-03T00:00:00Z",
+            &["checkout", "-q", "-b", "parser"],
+        );
+        commit("2021-01-03T00:00:00Z", "src/parser.rs", "Add parser");
+        commit("2021-01-04T00:00:00Z", "tests/parser.rs", "Test parser");
+        git(&root, "2021-01-05T00:00:00Z", &["checkout", "-q", "-"]);
+        git(
+            &root,
+            "2021-01-05T00:00:00Z",
+            &[
+                "merge",
+                "-q",
+                "--no-ff",
+                "-m",
+                "Merge pull request #2 from octocat/parser\n\nAdd a parser",
+                "parser",
+            ],
+        );
+        commit(
+            "2021-01-06T00:00:00Z",
+            "docs.md",
+            "Document the parser (#3)\n\n* Add docs",
+        );
+        commit("2021-01-07T00:00:00Z", "README.md", "Fix typo");
+
+        root
+    }
+
+    #[tokio::test]
+    async fn walks_changes_since_the_latest_tag() {
+        let root = repository();
+        let forge = LocalGit::new(root.clone(), None);
+        let config = crate::config::Config::new(String::from("octocat"), String::from("hello"));
+
+        // `release:latest` is resolved to the tag, which is walked from
+        // instead of its date.
+        let range = Range {
+            from: DateTime::<Utc>::from(std::time::UNIX_EPOCH),
+            to: Utc::now(),
+            from_reference: config
+                .from
+                .resolve(&forge, &config)
+                .await
+                .unwrap()
+                .reference,
+            to_reference: None,
+        };
+        assert_eq!(range.from_reference.as_deref(), Some("v0.1.0"));
+
+        let changes = forge
+            .merged_changes("octocat", "hello", &range)
+            .await
+            .unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (
+                    change.number,
+                    change.title.as_str(),
+                    change.user.login.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (3, "Document the parser", "Octocat"),
+                (2, "Add a parser", "octocat")
+            ]
+        );
+        assert_eq!(changes[0].body.as_deref(), Some("* Add docs"));
+        assert_eq!(changes[1].head_branch.as_deref(), Some("parser"));
+
+        let files = |change| forge.changed_files("octocat", "hello", change);
+        assert_eq!(files(&changes[0]).await.unwrap(), ["docs.md"]);
+        assert_eq!(
+            files(&changes[1]).await.unwrap(),
+            ["src/parser.rs", "tests/parser.rs"]
+        );
+
+        let messages = |change| forge.commit_messages("octocat", "hello", change);
+        assert_eq!(
+            messages(&changes[0]).await.unwrap(),
+            ["Document the parser (#3)\n\n* Add docs"]
+        );
+        assert_eq!(
+            messages(&changes[1]).await.unwrap(),
+            ["Test parser", "Add parser"]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn parses_merge_commits() {
+        assert_eq!(
+            MergeMessage::parse(
+                "Merge pull request #356 from XAMPPRocky/cargo\n\n\
+                 Update .cargo/config Shader Compilation Setup\n\nMore details"
+            ),
+            Some(MergeMessage {
+                number: 356,
+                title: String::from("Update .cargo/config Shader Compilation Setup"),
+                body: Some(String::from("More details")),
+                login: Some(String::from("XAMPPRocky")),
+                branch: Some(String::from("cargo")),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_squash_merge_commits() {
+        assert_eq!(
+            MergeMessage::parse("Upgrade winit v0.23 -> v0.24 (#353)\n\n* Bump winit\n"),
+            Some(MergeMessage {
+                number: 353,
+                title: String::from("Upgrade winit v0.23 -> v0.24"),
+                body: Some(String::from("* Bump winit")),
+                login: None,
+                branch: None,
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_other_commits() {
+        assert_eq!(MergeMessage::parse("Fix typo in README"), None);
+        assert_eq!(
+            MergeMessage::parse("Merge branch 'main' into feature"),
+            None
+        );
+    }
+}
@@ -1,3 +1,5 @@
+// Some of the standard lints below have since been renamed or removed.
+#![allow(renamed_and_removed_lints)]
 // BEGIN - Embark standard lints v0.3
 // do not change or add/remove here, but one can add exceptions after this section
 // for more info see: <https://github.com/EmbarkStudios/rust-ecosystem/issues/59>
@@ -51,25 +53,85 @@
 )]
 // END - Embark standard lints v0.3
 
+mod cargo;
+mod changelog;
 mod config;
+mod conventional;
 mod data;
+mod error;
+mod forge;
 
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use octocrab::Octocrab;
 use structopt::StructOpt;
 
 use config::timeframe::Timeframe;
 
+/// How to output the release notes.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    /// Rendered with the configured template.
+    Template,
+    /// The data given to the template, as JSON.
+    Json,
+    /// The data given to the template, as YAML.
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "template" => Ok(Self::Template),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            _ => Err(eyre::eyre!(
+                "Format must be one of `template`, `json`, or `yaml`."
+            )),
+        }
+    }
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Render release notes from data previously output with `--format
+    /// json` or `--format yaml`, without accessing the forge.
+    Render {
+        /// Path to the data. Files ending in `.yaml` or `.yml` are read as
+        /// YAML, and any other file as JSON.
+        #[structopt(long, parse(from_os_str))]
+        data: PathBuf,
+        /// Path to the template. Default: the configuration file's template,
+        /// or the default template.
+        #[structopt(long, parse(from_os_str))]
+        template: Option<PathBuf>,
+    },
+}
+
 #[derive(StructOpt)]
 /// Generate release notes for your project.
 struct Cli {
     /// Path to the configuration file. (Default: `None`)
-    #[structopt(short, long, parse(from_os_str))]
+    #[structopt(short, long, parse(from_os_str), global = true)]
     config: Option<PathBuf>,
-    /// The GitHub authenication token. (Default: `None`)
+    /// The forge's authenication token. Falls back to `GITHUB_TOKEN` or
+    /// `GITLAB_TOKEN` depending on the provider. (Default: `None`)
     #[structopt(short, long)]
     token: Option<String>,
+    /// The forge to get changes and releases from, one of `github`,
+    /// `gitlab`, or `local-git`. Default: `github`.
+    #[structopt(long)]
+    provider: Option<config::Provider>,
+    /// The base URL of a self-hosted forge. (Default: `None`)
+    #[structopt(long)]
+    provider_url: Option<String>,
+    /// Path to the repository used by the `local-git` provider. Default: `.`.
+    #[structopt(long, parse(from_os_str))]
+    repository_path: Option<PathBuf>,
     /// The start of the new release timeframe. Default: `release:latest`.
     #[structopt(long)]
     from: Option<Timeframe>,
@@ -79,68 +141,527 @@ struct Cli {
     /// Skip PRs if their labels match the regular expressions.
     #[structopt(long)]
     skip_labels: Option<Vec<String>>,
+    /// How many PRs to fetch the details of at once, per repository.
+    /// Default: `8`.
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+    /// Cache responses from the forge in this directory, so later runs only
+    /// check whether they changed. Default: `relnotes` in the user's cache
+    /// directory.
+    #[structopt(long, parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+    /// Generate release notes for each member crate of the Cargo workspace
+    /// with this manifest, since the crate's latest `<crate>-v<version>`
+    /// tag. Each crate's version is read from its manifest.
+    #[structopt(long, parse(from_os_str))]
+    workspace: Option<PathBuf>,
+    /// Write the release notes to this file instead of printing them. With
+    /// `--workspace` the path is relative to each crate's directory, unless
+    /// the format is `json` or `yaml`, which output one document for every
+    /// crate.
+    #[structopt(short, long, parse(from_os_str), global = true)]
+    output: Option<PathBuf>,
+    /// Insert the release notes into this changelog, after the marker line
+    /// or its title, unless it already has a section for the version. With
+    /// `--workspace` the path is relative to each crate's directory.
+    #[structopt(long, parse(from_os_str), conflicts_with = "output", global = true)]
+    prepend_to: Option<PathBuf>,
+    /// Output the release notes rendered with the template (`template`), or
+    /// the data given to the template as `json` or `yaml`. Default:
+    /// `template`.
+    #[structopt(long, default_value = "template", global = true)]
+    format: Format,
+    /// The line of the changelog to insert the release notes after.
+    /// Default: `<!-- next-header -->`.
+    #[structopt(long, global = true)]
+    marker: Option<String>,
     /// The repository and new version to generate release notes in the
     /// form `owner/repo@version`. `owner/repo@` is optional if provided
-    /// a configuration file.
-    repo_and_version: String,
-}
-
-fn initialise_github(token: Option<String>) -> eyre::Result<Octocrab> {
-    let mut builder = octocrab::Octocrab::builder();
-    let token = token.or_else(|| std::env::var("GITHUB_TOKEN").ok());
-    if let Some(token) = token {
-        builder = builder.personal_token(token);
-    }
-    Ok(builder.build()?)
+    /// a configuration file. A version of `cargo` reads the version from
+    /// `Cargo.toml`. If the version is omitted, it is read from the
+    /// configuration file's `version-from`, or computed from the release
+    /// `--from` and the PRs since.
+    repo_and_version: Option<String>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main]
-async fn main() -> eyre::Result<()> {
+async fn main() {
     env_logger::init_from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
     );
 
-    let cli = Cli::from_args();
-    let path = cli.config.map(|path| path.canonicalize()).transpose()?;
+    let result = match parse_args(std::env::args_os()) {
+        Ok(cli) => run(cli).await,
+        Err(error) => Err(error),
+    };
+    if let Err(error) = result {
+        eprintln!("Error: {:?}", error);
+        std::process::exit(error::exit_code(&error));
+    }
+}
+
+/// Parses the command line `args`, printing the help or version and exiting
+/// if they ask for either.
+fn parse_args<I>(args: I) -> eyre::Result<Cli>
+where
+    I: IntoIterator,
+    I::Item: Into<std::ffi::OsString> + Clone,
+{
+    match Cli::from_iter_safe(args) {
+        Ok(cli) => Ok(cli),
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => {
+            Err(error::Error::Config(error.message.trim_start_matches("error: ").to_owned()).into())
+        }
+    }
+}
 
-    let (mut config, version) = if let Some(path) = path {
+async fn run(cli: Cli) -> eyre::Result<()> {
+    let path = cli
+        .config
+        .as_ref()
+        .map(|path| {
+            path.canonicalize().map_err(|error| {
+                error::Error::Config(format!("Couldn't read `{}`: {}", path.display(), error))
+            })
+        })
+        .transpose()?;
+    let file = if let Some(path) = path {
         log::info!("Using configuration file found at `{}`.", path.display());
-        let string = tokio::fs::read_to_string(path).await?;
-        (
-            toml::from_str::<config::Config>(&string)?,
-            cli.repo_and_version,
+        let string = tokio::fs::read_to_string(&path).await?;
+        let config = toml::from_str::<config::Config>(&string)
+            .map_err(|error| error::Error::Config(format!("`{}`: {}", path.display(), error)))?;
+        Some(config)
+    } else {
+        None
+    };
+
+    if let Some(Command::Render { data, template }) = cli.command {
+        let template = match template {
+            Some(path) => tokio::fs::read_to_string(path).await?,
+            None => file.as_ref().map_or_else(
+                || config::Template::default().to_string(),
+                |config| config.template.to_string(),
+            ),
+        };
+        let marker = cli
+            .marker
+            .or_else(|| file.map(|config| config.changelog_marker))
+            .unwrap_or_else(|| String::from(changelog::DEFAULT_MARKER));
+        let string = tokio::fs::read_to_string(&data).await?;
+        let (workspace, data) = parse_data(&data, &string)?;
+        if workspace && cli.prepend_to.is_some() {
+            return Err(error::Error::Config(String::from(
+                "A workspace's data can't be prepended to a changelog, as it has a version for \
+                 each crate.",
+            ))
+            .into());
+        }
+
+        // A workspace's crates are rendered one after another.
+        let notes = data
+            .into_iter()
+            .map(|data| (PathBuf::new(), data))
+            .collect();
+        let (versions, rendered): (Vec<_>, Vec<_>) =
+            render_notes(cli.format, &template, workspace, notes)?
+                .into_iter()
+                .map(|(_, version, rendered)| (version, rendered))
+                .unzip();
+        return write(
+            cli.output.as_deref(),
+            cli.prepend_to.as_deref(),
+            &marker,
+            vec![(PathBuf::new(), versions.join(", "), rendered.join("\n"))],
         )
+        .await;
+    }
+
+    let (mut config, version) = if let Some(config) = file {
+        (config, cli.repo_and_version)
     } else {
-        let regex = regex::Regex::new(r"(?P<owner>\S+)/(?P<repo>\S+)@(?P<version>\S+)").unwrap();
-        let cap = regex.captures(&cli.repo_and_version).ok_or_else(|| {
-            eyre::eyre!("<repo_and_version> must be in `owner/repo@version` format.")
+        let regex = regex::Regex::new(r"^(?P<owner>\S+)/(?P<repo>[^/@\s]+)(?:@(?P<version>\S+))?$")
+            .unwrap();
+        let repo_and_version = cli.repo_and_version.unwrap_or_default();
+        let cap = regex.captures(&repo_and_version).ok_or_else(|| {
+            error::Error::Config(String::from(
+                "<repo_and_version> must be in `owner/repo[@version]` format.",
+            ))
         })?;
         let owner = cap.name("owner").unwrap().as_str().to_owned();
         let repo = cap.name("repo").unwrap().as_str().to_owned();
-        let version = cap.name("version").unwrap().as_str().to_owned();
+        let version = cap
+            .name("version")
+            .map(|version| version.as_str().to_owned());
 
         (config::Config::new(owner, repo), version)
     };
 
+    config.provider = cli.provider.unwrap_or(config.provider);
+    config.provider_url = cli.provider_url.or(config.provider_url);
+    config.repository_path = cli.repository_path.or(config.repository_path);
     config.from = cli.from.unwrap_or(config.from);
     config.to = cli.to.unwrap_or(config.to);
     config.skip_labels = cli
         .skip_labels
         .map(regex::RegexSet::new)
-        .transpose()?
+        .transpose()
+        .map_err(|error| error::Error::Config(format!("`--skip-labels`: {}", error)))?
         .unwrap_or(config.skip_labels);
+    config.workspace = cli.workspace.or(config.workspace);
+    config.jobs = cli.jobs.unwrap_or(config.jobs);
+    config.changelog_marker = cli.marker.unwrap_or(config.changelog_marker);
 
-    log::info!("Using `{}` as version number.", version);
-    let octocrab = initialise_github(cli.token)?;
-    let data = data::Data::from_config(&octocrab, version, &config).await?;
-    println!(
-        "{}",
-        tera::Tera::one_off(
-            &config.template,
-            &tera::Context::from_serialize(data)?,
-            false
-        )?
-    );
+    let version = match version.as_deref() {
+        Some("cargo") => config
+            .version_from
+            .clone()
+            .unwrap_or_default()
+            .version()
+            .map(Some),
+        Some(_) => Ok(version),
+        None => config
+            .version_from
+            .as_ref()
+            .map(config::VersionFrom::version)
+            .transpose(),
+    }
+    .map_err(|error| error::Error::Config(format!("Couldn't read the version: {}", error)))?;
+    if let Some(version) = &version {
+        log::info!("Using `{}` as version number.", version);
+    }
+    if cli.prepend_to.is_some() && cli.format != Format::Template {
+        return Err(error::Error::Config(String::from(
+            "Only the `template` format can be prepended to a changelog.",
+        ))
+        .into());
+    }
+    // Each crate's release notes are written relative to its directory.
+    let per_crate = config.workspace.is_some() && cli.format == Format::Template;
+    if let Some(path) = cli
+        .output
+        .iter()
+        .chain(&cli.prepend_to)
+        .find(|path| per_crate && path.is_absolute())
+    {
+        return Err(error::Error::Config(format!(
+            "`{}` must be relative to each crate's directory with a workspace.",
+            path.display()
+        ))
+        .into());
+    }
+
+    let forge = forge::from_config(&config, cli.token, cli.cache_dir)?;
+
+    let notes = if let Some(manifest) = &config.workspace {
+        if version.is_some() {
+            return Err(error::Error::Config(String::from(
+                "A version can't be given with a workspace, each crate's version is read from \
+                 its manifest.",
+            ))
+            .into());
+        }
+
+        data::Data::from_workspace(&*forge, &config, manifest).await?
+    } else {
+        vec![(
+            PathBuf::new(),
+            data::Data::from_config(&*forge, version, &config).await?,
+        )]
+    };
+
+    let notes = render_notes(
+        cli.format,
+        &config.template,
+        config.workspace.is_some(),
+        notes,
+    )?;
+    write(
+        cli.output.as_deref(),
+        cli.prepend_to.as_deref(),
+        &config.changelog_marker,
+        notes,
+    )
+    .await
+}
+
+/// Writes the release notes of each `(directory, version, rendered)` in
+/// `notes` to `output`, inserts them into the changelog at `prepend_to`, or
+/// otherwise prints them. The paths are relative to each directory. Nothing
+/// is written unless every changelog can take its release notes.
+async fn write(
+    output: Option<&Path>,
+    prepend_to: Option<&Path>,
+    marker: &str,
+    notes: Vec<(PathBuf, String, String)>,
+) -> eyre::Result<()> {
+    let mut files = Vec::new();
+    for (directory, version, rendered) in notes {
+        if let Some(path) = output {
+            let path = directory.join(path);
+            let message = format!("Wrote `{}` to `{}`.", version, path.display());
+            files.push((path, rendered, message));
+        } else if let Some(path) = prepend_to {
+            let path = directory.join(path);
+            let existing = match tokio::fs::read_to_string(&path).await {
+                Ok(existing) => existing,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(error) => return Err(error.into()),
+            };
+            let changelog = changelog::prepend(&existing, &rendered, &version, marker)
+                .map_err(|error| error.wrap_err(format!("`{}`", path.display())))?;
+            let message = format!("Added `{}` to `{}`.", version, path.display());
+            files.push((path, changelog, message));
+        } else {
+            println!("{}", rendered);
+        }
+    }
+
+    for (path, contents, message) in files {
+        tokio::fs::write(&path, contents).await?;
+        log::info!("{}", message);
+    }
 
     Ok(())
 }
+
+/// Parses the data at `path`, saved with `--format json` or `--format yaml`,
+/// of either a repository or each crate of a workspace. Returns whether it
+/// is a workspace's data.
+fn parse_data(path: &Path, string: &str) -> eyre::Result<(bool, Vec<data::Data>)> {
+    fn parse<T: serde::de::DeserializeOwned>(path: &Path, string: &str) -> eyre::Result<T> {
+        Ok(match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(string)?,
+            _ => serde_json::from_str(string)?,
+        })
+    }
+
+    match parse::<data::Data>(path, string) {
+        Ok(data) => Ok((false, vec![data])),
+        Err(error) => match parse::<BTreeMap<String, data::Data>>(path, string) {
+            Ok(crates) => Ok((true, crates.into_values().collect())),
+            // A repository's data is the more common, and its error the
+            // clearer.
+            Err(_) => Err(error),
+        },
+    }
+}
+
+/// Renders the release notes in `notes`, returning the directory, version
+/// and rendered release notes of each. The data of a workspace's crates is
+/// output as a single JSON or YAML document keyed by crate, as consecutive
+/// documents aren't valid JSON.
+fn render_notes(
+    format: Format,
+    template: &str,
+    workspace: bool,
+    notes: Vec<(PathBuf, data::Data)>,
+) -> eyre::Result<Vec<(PathBuf, String, String)>> {
+    if !workspace || format == Format::Template {
+        return notes
+            .into_iter()
+            .map(|(directory, data)| {
+                let version = data.version().to_owned();
+                Ok((directory, version, render(format, template, data)?))
+            })
+            .collect();
+    }
+
+    let versions = notes
+        .iter()
+        .map(|(_, data)| format!("{}@{}", data.title(), data.version()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let notes = notes
+        .into_iter()
+        .map(|(_, data)| (data.title().to_owned(), data))
+        .collect::<BTreeMap<_, _>>();
+    let rendered = if format == Format::Json {
+        serde_json::to_string_pretty(&notes)?
+    } else {
+        serde_yaml::to_string(&notes)?
+    };
+
+    Ok(vec![(PathBuf::new(), versions, rendered)])
+}
+
+fn render(format: Format, template: &str, data: data::Data) -> eyre::Result<String> {
+    Ok(match format {
+        Format::Template => {
+            tera::Tera::one_off(template, &tera::Context::from_serialize(data)?, false)
+                .map_err(error::Error::Template)?
+        }
+        Format::Json => serde_json::to_string_pretty(&data)?,
+        Format::Yaml => serde_yaml::to_string(&data)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(title: &str, version: &str) -> data::Data {
+        serde_json::from_value(serde_json::json!({
+            "breaking": [],
+            "categories": [],
+            "contributors": [],
+            "date": "2021-01-01",
+            "includes": [],
+            "owner": "EmbarkStudios",
+            "prs": [],
+            "repo": "crates",
+            "title": title,
+            "version": version,
+        }))
+        .unwrap()
+    }
+
+    fn workspace() -> Vec<(PathBuf, data::Data)> {
+        vec![
+            (PathBuf::from("crates/render"), data("render", "0.2.0")),
+            (PathBuf::from("crates/audio"), data("audio", "0.1.1")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn invalid_options_are_config_errors() {
+        let error = |args: &[&str]| {
+            let cli = Cli::from_iter(std::iter::once("relnotes").chain(args.iter().copied()));
+            async move { run(cli).await.unwrap_err() }
+        };
+
+        for args in [
+            &["--from", "nightly-release:latest"][..],
+            &["--jobs", "many"],
+        ] {
+            let arguments = parse_args(std::iter::once("relnotes").chain(args.iter().copied()))
+                .err()
+                .unwrap();
+            assert_eq!(error::exit_code(&arguments), 2, "{:?}", args);
+            assert!(arguments.to_string().contains(args[0]));
+        }
+
+        let skip_labels = error(&["EmbarkStudios/relnotes", "--skip-labels", "("]).await;
+        assert_eq!(error::exit_code(&skip_labels), 2);
+        assert!(skip_labels.to_string().contains("--skip-labels"));
+
+        let config = std::env::temp_dir().join(format!("relnotes-{}.toml", std::process::id()));
+        std::fs::write(
+            &config,
+            "owner = \"EmbarkStudios\"\nrepo = \"relnotes\"\n\
+             [template]\nstring = \"\"\n\
+             [version-from]\ncargo = \"missing/Cargo.toml\"\n",
+        )
+        .unwrap();
+        let version = error(&["--config", config.to_str().unwrap()]).await;
+        std::fs::remove_file(config).unwrap();
+        assert_eq!(error::exit_code(&version), 2);
+        assert!(version.to_string().contains("Couldn't read the version"));
+
+        let output = std::env::temp_dir().join("notes.md");
+        let absolute = error(&[
+            "EmbarkStudios/relnotes",
+            "--workspace",
+            "Cargo.toml",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .await;
+        assert_eq!(error::exit_code(&absolute), 2);
+        assert!(absolute.to_string().contains("relative to each crate"));
+    }
+
+    #[tokio::test]
+    async fn writes_nothing_unless_every_changelog_can_be_prepended_to() {
+        let root = std::env::temp_dir().join(format!("relnotes-prepend-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (render, audio) = (root.join("render"), root.join("audio"));
+        std::fs::create_dir_all(&render).unwrap();
+        std::fs::create_dir_all(&audio).unwrap();
+        std::fs::write(audio.join("CHANGELOG.md"), "## 0.1.1\n").unwrap();
+
+        let notes = vec![
+            (
+                render.clone(),
+                String::from("0.2.0"),
+                String::from("## 0.2.0"),
+            ),
+            (
+                audio.clone(),
+                String::from("0.1.1"),
+                String::from("## 0.1.1"),
+            ),
+        ];
+        let error = write(
+            None,
+            Some(Path::new("CHANGELOG.md")),
+            changelog::DEFAULT_MARKER,
+            notes,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(error::exit_code(&error), 2);
+        assert!(!render.join("CHANGELOG.md").exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn renders_workspace_data_as_one_document() {
+        let outputs = render_notes(Format::Json, "", true, workspace()).unwrap();
+        assert_eq!(outputs.len(), 1);
+        let (directory, versions, rendered) = &outputs[0];
+        assert_eq!(directory, Path::new(""));
+        assert_eq!(versions, "render@0.2.0, audio@0.1.1");
+        let json = serde_json::from_str::<BTreeMap<String, data::Data>>(rendered).unwrap();
+        assert_eq!(json["render"].version(), "0.2.0");
+        assert_eq!(json["audio"].version(), "0.1.1");
+
+        let (is_workspace, data) = parse_data(Path::new("data.json"), rendered).unwrap();
+        assert!(is_workspace);
+        assert_eq!(
+            data.iter().map(data::Data::title).collect::<Vec<_>>(),
+            ["audio", "render"]
+        );
+
+        let outputs = render_notes(Format::Yaml, "", true, workspace()).unwrap();
+        assert_eq!(outputs.len(), 1);
+        let yaml = serde_yaml::from_str::<BTreeMap<String, data::Data>>(&outputs[0].2).unwrap();
+        assert_eq!(yaml.keys().collect::<Vec<_>>(), ["audio", "render"]);
+    }
+
+    #[test]
+    fn renders_workspace_templates_per_crate() {
+        let outputs = render_notes(
+            Format::Template,
+            "{{ title }} {{ version }}",
+            true,
+            workspace(),
+        )
+        .unwrap();
+        assert_eq!(
+            outputs,
+            [
+                (
+                    PathBuf::from("crates/render"),
+                    String::from("0.2.0"),
+                    String::from("render 0.2.0")
+                ),
+                (
+                    PathBuf::from("crates/audio"),
+                    String::from("0.1.1"),
+                    String::from("audio 0.1.1")
+                ),
+            ]
+        );
+
+        let outputs = render_notes(Format::Json, "", false, vec![workspace().remove(0)]).unwrap();
+        let (is_workspace, data) = parse_data(Path::new("data.json"), &outputs[0].2).unwrap();
+        assert!(!is_workspace);
+        assert_eq!(data[0].version(), "0.2.0");
+        let json = serde_json::from_str::<data::Data>(&outputs[0].2).unwrap();
+        assert_eq!(json.version(), "0.2.0");
+    }
+}